@@ -189,8 +189,75 @@ class PerformanceBenchmark:
             "python": python_results,
             "rust": rust_results,
             "improvements": improvements,
+            "scoring": self.benchmark_memory_scoring(),
         }
 
+    def benchmark_memory_scoring(self, items: int = 20000, queries: int = 20) -> Dict[str, float]:
+        """
+        Benchmark similarity scoring in the native memory store on synthetic corpora.
+
+        Unlike ``benchmark_memory_storage`` this calls ``RustMemoryStorage`` directly,
+        so the numbers isolate the Rust scoring path. Corpora and queries come from a
+        fixed seed; run the same benchmark on two builds to compare them.
+
+        Cases (milliseconds per query):
+        - ``dense_tf_cosine_ms``: 8-term queries over items of 60 words drawn from a
+          300-word vocabulary, so most items share query terms, scored by plain TF
+          cosine (below ``min_docs_for_idf``)
+        - ``sparse_tf_cosine_ms``: 2-term queries over items of 30 words drawn from a
+          50,000-word vocabulary, so few items share a query term, by plain TF cosine
+        - ``sparse_tfidf_stale_ms``: the sparse queries by TF-IDF, each right after a
+          save so every cached item norm is stale (the save is included)
+        - ``sparse_tfidf_warm_ms``: the sparse queries by TF-IDF with warm norms
+
+        Args:
+            items: Number of stored items per corpus
+            queries: Number of queries timed per case
+
+        Returns:
+            Dictionary of per-query timings, empty if the Rust extension is unavailable
+        """
+        try:
+            from ._core import RustMemoryStorage
+        except ImportError:
+            return {}
+
+        rng = random.Random(42)
+        results: Dict[str, float] = {}
+
+        def time_queries(storage: Any, query_texts: List[str]) -> float:
+            storage.search_full(query_texts[0], 5)  # warm up
+            start_time = time.perf_counter()
+            for query in query_texts:
+                storage.search_full(query, 5)
+            return (time.perf_counter() - start_time) / len(query_texts) * 1000
+
+        dense_vocab = [f"w{i}" for i in range(300)]
+        dense = RustMemoryStorage(min_docs_for_idf=items + 1)
+        dense.save_batch([" ".join(rng.choices(dense_vocab, k=60)) for _ in range(items)])
+        dense_queries = [" ".join(rng.choices(dense_vocab, k=8)) for _ in range(queries)]
+        results["dense_tf_cosine_ms"] = round(time_queries(dense, dense_queries), 2)
+
+        sparse_vocab = [f"w{i}" for i in range(50000)]
+        sparse_items = [" ".join(rng.choices(sparse_vocab, k=30)) for _ in range(items)]
+        sparse_queries = [" ".join(rng.choices(sparse_vocab, k=2)) for _ in range(queries)]
+
+        sparse = RustMemoryStorage(min_docs_for_idf=items + queries + 2)
+        sparse.save_batch(sparse_items)
+        results["sparse_tf_cosine_ms"] = round(time_queries(sparse, sparse_queries), 2)
+
+        sparse_tfidf = RustMemoryStorage()
+        sparse_tfidf.save_batch(sparse_items)
+        start_time = time.perf_counter()
+        for query in sparse_queries:
+            sparse_tfidf.save(" ".join(rng.choices(sparse_vocab, k=30)))
+            sparse_tfidf.search_full(query, 5)
+        stale_ms = (time.perf_counter() - start_time) / len(sparse_queries) * 1000
+        results["sparse_tfidf_stale_ms"] = round(stale_ms, 2)
+        results["sparse_tfidf_warm_ms"] = round(time_queries(sparse_tfidf, sparse_queries), 2)
+
+        return results
+
     def _calculate_improvements(
         self, python_results: Dict[str, Any], rust_results: Dict[str, Any]
     ) -> Dict[str, Any]:
@@ -1233,6 +1300,8 @@ print(f"{{{{MEMORY_MB}}}}:{peak_mb / 1024 / 1024}")
             print(f"  Rust: {rust_save:.0f} saves/sec")
             improvement = results["memory"]["improvements"]["save_time"]
             print(f"  Improvement: {improvement:.1f}x")
+        for case, ms_per_query in results["memory"]["scoring"].items():
+            print(f"  Scoring {case}: {ms_per_query:.1f}")
 
         # Tool execution benchmark
         print("\nBenchmarking tool execution...")
@@ -7,976 +7,6760 @@ use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
 
+/// FNV-1a 64-bit hasher - unlike std's DefaultHasher its output is stable across
+/// Rust releases, so it is safe for persisted or compared digests (feed integers as
+/// explicit little-endian bytes to keep digests platform-independent)
+struct Fnv1aHasher(u64);
+
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Fnv1aHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl std::hash::Hasher for Fnv1aHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Version of the persisted formats this crate writes: task graph and conversation JSON
+/// (`to_json`, also used for task checkpoints), memory journals and memory files
+/// (`save_to_file`), tool-cache manifests (`export_manifest`) and cache files
+/// (`export_cache`), and tool stats snapshots (`stats_snapshot`). Every artifact records
+/// it as `format_version`.
+///
+/// Bump it whenever one of these formats changes in a way older readers would misread,
+/// and teach the loaders to migrate files from the previous version. Artifacts written
+/// before versioning carry no version field and are read as version 1.
+const FORMAT_VERSION: u64 = 1;
+
+fn default_format_version() -> u64 {
+    FORMAT_VERSION
+}
+
+// Helper function to reject artifacts written by a newer (or bogus) format version
+fn check_format_version(version: u64) -> PyResult<()> {
+    if version == 0 || version > FORMAT_VERSION {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "unsupported format version {} (this build reads versions 1 to {})",
+            version, FORMAT_VERSION
+        )));
+    }
+    Ok(())
+}
+
+// Helper function to load a versioned JSON artifact. The version is checked before the
+// rest is interpreted, so a newer format fails with a clear error rather than a confusing
+// deserialization one.
+fn parse_versioned<T: serde::de::DeserializeOwned>(json_str: &str) -> PyResult<T> {
+    let value: serde_json::Value = serde_json::from_str(json_str).map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+            "Failed to deserialize from JSON: {}",
+            e
+        ))
+    })?;
+
+    let version = match value.get("format_version") {
+        None => 1,
+        Some(version) => version.as_u64().ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid format_version {}",
+                version
+            ))
+        })?,
+    };
+    check_format_version(version)?;
+
+    serde_json::from_value(value).map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+            "Failed to deserialize from JSON: {}",
+            e
+        ))
+    })
+}
+
+/// Source of `CorpusStats` generation stamps. Stamps are process-wide, so one is never
+/// reused even when a store's corpus is rebuilt from scratch.
+static CORPUS_GENERATION: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// An item's TF-IDF vector norm, cached together with the corpus generation it was
+/// computed for. Atomic so searches can fill it in while holding only read locks.
+/// Every IDF weight depends on the item count, so each save or delete really does change
+/// every norm: the cache saves work across runs of searches between writes, not across
+/// interleaved saves and searches.
+#[derive(Debug)]
+struct NormCache {
+    /// Generation the norm is valid for (u64::MAX = nothing cached)
+    generation: std::sync::atomic::AtomicU64,
+    norm_bits: std::sync::atomic::AtomicU64,
+}
+
+impl Default for NormCache {
+    fn default() -> Self {
+        NormCache {
+            generation: std::sync::atomic::AtomicU64::new(u64::MAX),
+            norm_bits: std::sync::atomic::AtomicU64::new(0),
+        }
+    }
+}
+
+impl Clone for NormCache {
+    fn clone(&self) -> Self {
+        NormCache {
+            generation: std::sync::atomic::AtomicU64::new(self.generation.load(std::sync::atomic::Ordering::SeqCst)),
+            norm_bits: std::sync::atomic::AtomicU64::new(self.norm_bits.load(std::sync::atomic::Ordering::SeqCst)),
+        }
+    }
+}
+
+/// Item access counter, atomic so searches can bump it while holding only a read lock
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AccessCount(std::sync::atomic::AtomicU64);
+
+impl Clone for AccessCount {
+    fn clone(&self) -> Self {
+        AccessCount(std::sync::atomic::AtomicU64::new(self.get()))
+    }
+}
+
+impl AccessCount {
+    fn get(&self) -> u64 {
+        self.0.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    fn increment(&self) {
+        self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
 // Add a new struct to store memory items with metadata
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 struct MemoryItem {
     id: u64,
     content: String,
     // Store word frequencies for TF-IDF computation
     word_frequencies: HashMap<String, f64>,
+    // Creation time in seconds since the Unix epoch
+    created_at: u64,
+    // Number of tokens in the item (document length for BM25)
+    token_count: usize,
+    // Times the item was returned by `search` or `search_with_activation`
+    access_count: AccessCount,
+    // TF-IDF norm, recomputed only after the corpus changes
+    #[serde(skip)]
+    tfidf_norm: NormCache,
+    // Plain term-frequency norm for cosine scoring, set at save time (and filled on first
+    // use for items loaded from a file)
+    #[serde(skip)]
+    term_norm: std::sync::OnceLock<f64>,
 }
 
-/// A high-performance memory storage system
-#[pyclass]
-pub struct RustMemoryStorage {
-    data: Arc<Mutex<Vec<MemoryItem>>>,
-    next_id: Arc<Mutex<u64>>,
-}
-
-impl RustMemoryStorage {
-    // Helper function to compute word frequencies for TF-IDF (private, not exposed to Python)
-    fn compute_word_frequencies(&self, text: &str) -> HashMap<String, f64> {
-        let mut frequencies = HashMap::new();
-
-        // Tokenize and convert to lowercase
-        let lower_text = text.to_lowercase();
-        let tokens: Vec<String> = lower_text
-            .split(|c: char| c.is_whitespace() || c == '.' || c == ',' || c == '!' || c == '?' || c == ';' || c == ':' || c == '(' || c == ')')
-            .filter(|s| !s.is_empty())
-            .map(|s| s.to_string())
-            .collect();
+impl MemoryItem {
+    // Estimated heap + inline footprint: the struct itself, the content bytes, and
+    // each frequency entry (key bytes plus the String and f64 it is stored as)
+    fn estimated_bytes(&self) -> usize {
+        let entry_overhead = std::mem::size_of::<String>() + std::mem::size_of::<f64>();
+        std::mem::size_of::<MemoryItem>()
+            + self.content.len()
+            + self
+                .word_frequencies
+                .keys()
+                .map(|term| term.len() + entry_overhead)
+                .sum::<usize>()
+    }
 
-        for token in tokens {
-            *frequencies.entry(token).or_insert(0.0) += 1.0;
+    // The item's TF-IDF norm under `corpus`, from the cache when the corpus hasn't changed
+    // since it was computed. Concurrent searches hold the corpus read lock, so they can
+    // only ever fill the cache with the same value for the same generation.
+    fn tfidf_norm(&self, corpus: &CorpusStats) -> f64 {
+        if self.tfidf_norm.generation.load(std::sync::atomic::Ordering::SeqCst) == corpus.generation {
+            return f64::from_bits(self.tfidf_norm.norm_bits.load(std::sync::atomic::Ordering::SeqCst));
         }
+        let norm = RustMemoryStorage::calculate_tfidf_norm(&self.word_frequencies, corpus);
+        self.tfidf_norm.norm_bits.store(norm.to_bits(), std::sync::atomic::Ordering::SeqCst);
+        self.tfidf_norm.generation.store(corpus.generation, std::sync::atomic::Ordering::SeqCst);
+        norm
+    }
 
-        frequencies
+    // The item's plain term-frequency norm, which doesn't depend on the corpus
+    fn term_norm(&self) -> f64 {
+        *self
+            .term_norm
+            .get_or_init(|| RustMemoryStorage::calculate_term_norm(&self.word_frequencies))
     }
+}
 
-    // Helper function to calculate cosine similarity between two word frequency maps (private, not exposed to Python)
-    fn calculate_cosine_similarity(&self, query_freq: &HashMap<String, f64>, item_freq: &HashMap<String, f64>) -> f64 {
-        // Get all unique terms from both documents
-        let mut all_terms = std::collections::HashSet::new();
-        for term in query_freq.keys() {
-            all_terms.insert(term);
-        }
-        for term in item_freq.keys() {
-            all_terms.insert(term);
-        }
+/// A vocabulary plus one `(item id, vector)` per item, as exported by `export_vectors`
+type ExportedVectors<V> = (Vec<String>, Vec<(u64, V)>);
 
-        // Calculate cosine similarity
-        let mut dot_product = 0.0;
-        let mut query_norm = 0.0;
-        let mut item_norm = 0.0;
+/// Estimated bytes held by all RustMemoryStorage instances in this process
+static GLOBAL_MEMORY_BYTES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
 
-        for term in &all_terms {
-            let query_tf = *query_freq.get(*term).unwrap_or(&0.0);
-            let item_tf = *item_freq.get(*term).unwrap_or(&0.0);
+/// Process-wide cap on `GLOBAL_MEMORY_BYTES` enforced by `save` (usize::MAX = no cap)
+static GLOBAL_MEMORY_CAP: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(usize::MAX);
 
-            dot_product += query_tf * item_tf;
-            query_norm += query_tf * query_tf;
-            item_norm += item_tf * item_tf;
-        }
+/// Estimated bytes held by all memory stores in this process.
+///
+/// Each store accounts for its items as in `RustMemoryStorage.memory_usage_bytes`,
+/// registering on `save` and releasing on `clear` or when the store is dropped.
+#[pyfunction]
+fn total_memory_bytes() -> usize {
+    GLOBAL_MEMORY_BYTES.load(std::sync::atomic::Ordering::SeqCst)
+}
 
-        if query_norm == 0.0 || item_norm == 0.0 {
-            return 0.0; // No similarity if one vector is zero
-        }
+/// Set (or with `None`, remove) a process-wide byte budget across all memory stores.
+/// Saves that would push `total_memory_bytes()` past it raise `MemoryError`.
+#[pyfunction]
+#[pyo3(signature = (cap_bytes=None))]
+fn set_global_memory_cap(cap_bytes: Option<usize>) {
+    GLOBAL_MEMORY_CAP.store(cap_bytes.unwrap_or(usize::MAX), std::sync::atomic::Ordering::SeqCst);
+}
 
-        dot_product / (query_norm.sqrt() * item_norm.sqrt())
-    }
+/// Built-in English stopword list, enabled with `english_stopwords=True`
+const ENGLISH_STOPWORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "an", "and", "any", "are", "as", "at", "be",
+    "been", "but", "by", "can", "could", "did", "do", "does", "for", "from", "had", "has",
+    "have", "he", "her", "his", "how", "i", "if", "in", "into", "is", "it", "its", "just",
+    "me", "my", "no", "not", "of", "on", "or", "our", "she", "so", "than", "that", "the",
+    "their", "them", "then", "there", "these", "they", "this", "to", "up", "was", "we",
+    "were", "what", "when", "which", "who", "will", "with", "would", "you", "your",
+];
+
+/// Tokenization settings shared by save and search
+#[derive(Debug, Clone)]
+struct TokenizerConfig {
+    lowercase: bool,
+    /// Tokens with fewer characters than this are dropped
+    min_token_len: usize,
+    /// Lowercased tokens dropped before counting (matched case-insensitively)
+    stopwords: std::collections::HashSet<String>,
+    /// Split runs of CJK characters into overlapping character bigrams
+    cjk_mode: bool,
 }
 
-#[pymethods]
-impl RustMemoryStorage {
-    #[new]
-    pub fn new() -> Self {
-        RustMemoryStorage {
-            data: Arc::new(Mutex::new(Vec::new())),
-            next_id: Arc::new(Mutex::new(0)),
+impl Default for TokenizerConfig {
+    fn default() -> Self {
+        TokenizerConfig {
+            lowercase: true,
+            min_token_len: 1,
+            stopwords: std::collections::HashSet::new(),
+            cjk_mode: false,
         }
     }
+}
 
-    pub fn save(&self, value: &str) -> PyResult<()> {
-        let mut data = self.data.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to acquire lock: {}",
-                e
-            ))
-        })?;
-
-        let mut next_id = self.next_id.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to acquire id lock: {}",
-                e
-            ))
-        })?;
+// Helper function to detect characters from scripts written without spaces between
+// words: CJK ideographs, kana and Hangul
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{11FF}'     // Hangul Jamo
+        | '\u{3040}'..='\u{30FF}'   // Hiragana, Katakana
+        | '\u{3130}'..='\u{318F}'   // Hangul Compatibility Jamo
+        | '\u{31F0}'..='\u{31FF}'   // Katakana Phonetic Extensions
+        | '\u{3400}'..='\u{4DBF}'   // CJK Unified Ideographs Extension A
+        | '\u{4E00}'..='\u{9FFF}'   // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}'   // Hangul Syllables
+        | '\u{F900}'..='\u{FAFF}'   // CJK Compatibility Ideographs
+        | '\u{20000}'..='\u{2FA1F}' // Supplementary ideographs
+    )
+}
 
-        // Create word frequency map for TF-IDF
-        let word_frequencies = self.compute_word_frequencies(value);
+// Helper function to detect CJK punctuation (、。「」, fullwidth ，！？ etc.), which
+// separates tokens in CJK mode
+fn is_cjk_punctuation(c: char) -> bool {
+    matches!(c, '\u{3000}'..='\u{303F}') || (matches!(c, '\u{FF00}'..='\u{FFEF}') && !c.is_alphanumeric())
+}
 
-        let item = MemoryItem {
-            id: *next_id,
-            content: value.to_string(),
-            word_frequencies,
+impl TokenizerConfig {
+    // Helper function to split text into tokens, in order, applying every setting
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        // Tokenize and optionally convert to lowercase
+        let text = if self.lowercase {
+            text.to_lowercase()
+        } else {
+            text.to_string()
         };
+        let words = text
+            .split(|c: char| {
+                c.is_whitespace() || c == '.' || c == ',' || c == '!' || c == '?' || c == ';' || c == ':' || c == '(' || c == ')'
+                    || (self.cjk_mode && is_cjk_punctuation(c))
+            })
+            .filter(|s| !s.is_empty());
+        if !self.cjk_mode {
+            return words
+                .filter(|s| s.chars().count() >= self.min_token_len)
+                .filter(|s| !self.is_stopword(s))
+                .map(|s| s.to_string())
+                .collect();
+        }
 
-        data.push(item);
-        *next_id += 1;
+        let mut tokens = Vec::new();
+        for word in words {
+            self.push_cjk_segments(word, &mut tokens);
+        }
+        tokens
+    }
 
-        Ok(())
+    // Helper function for CJK mode: split a word into runs of CJK and other characters.
+    // Other runs become ordinary tokens; a CJK run becomes its overlapping character
+    // bigrams (or the lone character, for a run of one), exempt from `min_token_len`.
+    fn push_cjk_segments(&self, word: &str, tokens: &mut Vec<String>) {
+        let chars: Vec<char> = word.chars().collect();
+        let mut start = 0;
+        while start < chars.len() {
+            let cjk = is_cjk(chars[start]);
+            let end = chars[start..]
+                .iter()
+                .position(|c| is_cjk(*c) != cjk)
+                .map_or(chars.len(), |offset| start + offset);
+            let run = &chars[start..end];
+
+            if !cjk {
+                let token: String = run.iter().collect();
+                if run.len() >= self.min_token_len && !self.is_stopword(&token) {
+                    tokens.push(token);
+                }
+            } else if run.len() == 1 {
+                let token = run[0].to_string();
+                if !self.is_stopword(&token) {
+                    tokens.push(token);
+                }
+            } else {
+                for pair in run.windows(2) {
+                    let token: String = pair.iter().collect();
+                    if !self.is_stopword(&token) {
+                        tokens.push(token);
+                    }
+                }
+            }
+            start = end;
+        }
     }
 
-    pub fn get_all(&self) -> PyResult<Vec<String>> {
-        let data = self.data.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to acquire lock: {}",
-                e
-            ))
-        })?;
-        Ok(data.iter().map(|item| item.content.clone()).collect())
+    // Helper function to compute word frequencies for TF-IDF
+    fn compute_word_frequencies(&self, text: &str) -> HashMap<String, f64> {
+        let mut frequencies = HashMap::new();
+        for token in self.tokenize(text) {
+            *frequencies.entry(token).or_insert(0.0) += 1.0;
+        }
+        frequencies
     }
 
-    pub fn search(&self, query: &str, limit: usize) -> PyResult<Vec<String>> {
-        let data = self.data.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to acquire lock: {}",
-                e
-            ))
-        })?;
+    // Helper function to check a token against the stopword set
+    fn is_stopword(&self, token: &str) -> bool {
+        if self.stopwords.is_empty() {
+            return false;
+        }
+        if self.lowercase {
+            self.stopwords.contains(token)
+        } else {
+            self.stopwords.contains(&token.to_lowercase())
+        }
+    }
 
-        // Compute query word frequencies
-        let query_frequencies = self.compute_word_frequencies(query);
+    // Helper function to build the stopword set from a user list and/or the English default
+    fn build_stopwords(stopwords: Option<Vec<String>>, english_stopwords: bool) -> std::collections::HashSet<String> {
+        let mut set: std::collections::HashSet<String> = stopwords
+            .unwrap_or_default()
+            .iter()
+            .map(|word| word.to_lowercase())
+            .collect();
+        if english_stopwords {
+            set.extend(ENGLISH_STOPWORDS.iter().map(|word| word.to_string()));
+        }
+        set
+    }
+}
 
-        // Calculate similarity scores for each item
-        let mut scored_results: Vec<(String, f64)> = Vec::new();
+/// Split text into tokens exactly as `RustMemoryStorage` does for saved items and queries.
+///
+/// With the default arguments this is the default store tokenization: lowercased, split
+/// on whitespace and `.,!?;:()`, empty tokens dropped. The keyword arguments mirror the
+/// store's constructor options, so passing the same values reproduces a configured
+/// store's tokens. Tokens are returned in order with duplicates kept.
+#[pyfunction]
+#[pyo3(signature = (text, lowercase=true, min_token_len=1, stopwords=None, english_stopwords=false, cjk_mode=false))]
+fn tokenize(
+    text: &str,
+    lowercase: bool,
+    min_token_len: usize,
+    stopwords: Option<Vec<String>>,
+    english_stopwords: bool,
+    cjk_mode: bool,
+) -> Vec<String> {
+    TokenizerConfig {
+        lowercase,
+        min_token_len,
+        stopwords: TokenizerConfig::build_stopwords(stopwords, english_stopwords),
+        cjk_mode,
+    }
+    .tokenize(text)
+}
 
-        for item in &*data {
-            let similarity = self.calculate_cosine_similarity(&query_frequencies, &item.word_frequencies);
-            scored_results.push((item.content.clone(), similarity));
-        }
+/// Running corpus aggregates maintained incrementally as items are added and removed
+#[derive(Debug, Clone, Default)]
+struct CorpusStats {
+    /// Number of stored items containing each term
+    document_frequencies: HashMap<String, usize>,
+    /// Total occurrences of each term across all stored items
+    term_totals: HashMap<String, f64>,
+    total_tokens: usize,
+    /// Number of stored items
+    document_count: usize,
+    /// Stamp from `CORPUS_GENERATION`, replaced on every change so cached norms go stale
+    generation: u64,
+}
 
-        // Sort by similarity score (descending)
-        scored_results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+impl CorpusStats {
+    // Take a fresh generation stamp after any change to the aggregates
+    fn bump_generation(&mut self) {
+        self.generation = CORPUS_GENERATION.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+    }
 
-        // Take top results up to limit
-        let results: Vec<String> = scored_results
-            .into_iter()
-            .take(limit)
-            .map(|(content, _)| content)
-            .collect();
+    fn add_document(&mut self, word_frequencies: &HashMap<String, f64>) {
+        self.bump_generation();
+        self.document_count += 1;
+        for (term, count) in word_frequencies {
+            *self.document_frequencies.entry(term.clone()).or_insert(0) += 1;
+            *self.term_totals.entry(term.clone()).or_insert(0.0) += count;
+            self.total_tokens += *count as usize;
+        }
+    }
 
-        Ok(results)
+    fn remove_document(&mut self, word_frequencies: &HashMap<String, f64>) {
+        self.bump_generation();
+        self.document_count = self.document_count.saturating_sub(1);
+        for (term, count) in word_frequencies {
+            // Drop terms that no longer appear in any item so the vocabulary stays exact
+            if let Some(df) = self.document_frequencies.get_mut(term) {
+                *df -= 1;
+                if *df == 0 {
+                    self.document_frequencies.remove(term);
+                    self.term_totals.remove(term);
+                } else if let Some(total) = self.term_totals.get_mut(term) {
+                    *total -= count;
+                }
+            }
+            self.total_tokens = self.total_tokens.saturating_sub(*count as usize);
+        }
     }
-}
 
-/// Tool execution result for caching
-#[derive(Debug, Clone)]
-struct CachedResult {
-    result: String,
-    timestamp: std::time::Instant,
+    // Mean item length in tokens (0.0 for an empty corpus)
+    fn average_document_length(&self) -> f64 {
+        if self.document_count == 0 {
+            0.0
+        } else {
+            self.total_tokens as f64 / self.document_count as f64
+        }
+    }
 }
 
-/// A high-performance tool execution engine with caching and validation
-#[pyclass]
-pub struct RustToolExecutor {
-    max_recursion_depth: usize,
-    execution_count: Arc<Mutex<usize>>,
-    /// Cache for tool results (tool_name + args_hash -> result)
-    result_cache: Arc<Mutex<HashMap<String, CachedResult>>>,
-    /// Cache TTL in seconds
-    cache_ttl_secs: u64,
-    /// Maximum cache size (0 = unlimited) - using AtomicUsize for thread-safe interior mutability
-    max_cache_size: std::sync::atomic::AtomicUsize,
-    /// Execution statistics
-    stats: Arc<Mutex<ExecutionStats>>,
+// Helper function to count the tokens behind a word frequency map
+fn token_count(word_frequencies: &HashMap<String, f64>) -> usize {
+    word_frequencies.values().map(|count| *count as usize).sum()
 }
 
-#[derive(Debug, Clone, Default)]
-struct ExecutionStats {
-    total_executions: usize,
-    cache_hits: usize,
-    cache_misses: usize,
-    validation_failures: usize,
+/// Term-vector similarity metrics shared by the in-memory store and the FTS rerank stage
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SimilarityMetric {
+    /// Cosine of the plain term-frequency vectors (no IDF weighting)
+    Cosine,
+    /// Shared distinct terms over all distinct terms
+    Jaccard,
+    /// Number of distinct query terms present in the item
+    Overlap,
 }
 
-#[pymethods]
-impl RustToolExecutor {
-    #[new]
-    #[pyo3(signature = (max_recursion_depth, cache_ttl_secs=300))]
-    pub fn new(max_recursion_depth: usize, cache_ttl_secs: u64) -> Self {
-        RustToolExecutor {
-            max_recursion_depth,
-            execution_count: Arc::new(Mutex::new(0)),
-            result_cache: Arc::new(Mutex::new(HashMap::new())),
-            cache_ttl_secs,
-            max_cache_size: std::sync::atomic::AtomicUsize::new(1000), // Default max cache size
-            stats: Arc::new(Mutex::new(ExecutionStats::default())),
+impl SimilarityMetric {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "cosine" => Ok(SimilarityMetric::Cosine),
+            "jaccard" => Ok(SimilarityMetric::Jaccard),
+            "overlap" => Ok(SimilarityMetric::Overlap),
+            other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid similarity metric '{}': expected 'cosine', 'jaccard' or 'overlap'",
+                other
+            ))),
         }
     }
 
-    /// Set the maximum cache size (0 = unlimited)
-    pub fn set_max_cache_size(&self, max_size: usize) -> PyResult<()> {
-        self.max_cache_size.store(max_size, std::sync::atomic::Ordering::SeqCst);
-        Ok(())
+    fn score(self, query_freq: &HashMap<String, f64>, item_freq: &HashMap<String, f64>) -> f64 {
+        match self {
+            SimilarityMetric::Cosine => RustMemoryStorage::calculate_cosine_similarity(
+                query_freq,
+                RustMemoryStorage::calculate_term_norm(query_freq),
+                item_freq,
+                RustMemoryStorage::calculate_term_norm(item_freq),
+            ),
+            SimilarityMetric::Jaccard => RustMemoryStorage::calculate_jaccard_similarity(query_freq, item_freq),
+            SimilarityMetric::Overlap => RustMemoryStorage::calculate_term_overlap(query_freq, item_freq),
+        }
     }
+}
 
-    /// Get the current maximum cache size
-    pub fn get_max_cache_size(&self) -> usize {
-        self.max_cache_size.load(std::sync::atomic::Ordering::SeqCst)
-    }
+/// One record in a RustMemoryStorage operation journal (one JSON object per line)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum JournalRecord {
+    /// First record of every journal
+    Header { format_version: u64 },
+    Save {
+        id: u64,
+        content: String,
+        /// Creation time in Unix seconds (absent from journals written before it was
+        /// recorded; those items are stamped with the replay time)
+        #[serde(default)]
+        created_at: Option<u64>,
+    },
+    Delete { id: u64 },
+    Clear,
+}
 
-    /// Validate JSON arguments - returns parsed JSON or error message
-    pub fn validate_args(&self, args_json: &str) -> PyResult<bool> {
-        match serde_json::from_str::<serde_json::Value>(args_json) {
-            Ok(_) => Ok(true),
-            Err(e) => {
-                let mut stats = self.stats.lock().map_err(|e| {
-                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
-                })?;
-                stats.validation_failures += 1;
-                Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
-                    "Invalid JSON arguments: {}",
-                    e
-                )))
-            }
+/// Append handle for a store's operation journal
+struct JournalWriter {
+    path: String,
+    file: std::fs::File,
+    /// fsync after every record instead of leaving durability to the OS
+    fsync: bool,
+}
+
+impl JournalWriter {
+    fn open(path: &str, fsync: bool) -> PyResult<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                    "Failed to open journal '{}': {}",
+                    path, e
+                ))
+            })?;
+        let is_new = file.metadata().map(|meta| meta.len() == 0).unwrap_or(false);
+        let mut writer = JournalWriter {
+            path: path.to_string(),
+            file,
+            fsync,
+        };
+        if is_new {
+            writer.append(&JournalRecord::Header {
+                format_version: FORMAT_VERSION,
+            })?;
         }
+        Ok(writer)
     }
 
-    /// Parse and normalize JSON arguments for consistent caching
-    pub fn parse_args(&self, args_json: &str) -> PyResult<String> {
-        let value: serde_json::Value = serde_json::from_str(args_json).map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
-                "Invalid JSON: {}",
-                e
-            ))
-        })?;
+    fn append(&mut self, record: &JournalRecord) -> PyResult<()> {
+        use std::io::Write;
 
-        // Re-serialize with sorted keys for consistent cache keys
-        serde_json::to_string(&value).map_err(|e| {
+        let mut line = serde_json::to_string(record).map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to serialize: {}",
+                "Failed to serialize journal record: {}",
                 e
             ))
-        })
+        })?;
+        line.push('\n');
+
+        // A single write per record, so a crash can only truncate the last line
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|_| if self.fsync { self.file.sync_data() } else { Ok(()) })
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                    "Failed to write journal '{}': {}",
+                    self.path, e
+                ))
+            })
     }
+}
 
-    /// Check if we can execute (recursion depth check)
-    pub fn can_execute(&self) -> PyResult<bool> {
-        let count = self.execution_count.lock().map_err(|e| {
+/// On-disk form of a store's items, used by `save_to_file` and `load_from_file`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MemoryStoreFile {
+    #[serde(default = "default_format_version")]
+    format_version: u64,
+    /// The store's next id at save time, so ids of deleted items stay retired
+    #[serde(default)]
+    next_id: u64,
+    items: Vec<MemoryItem>,
+}
+
+/// A high-performance memory storage system
+#[pyclass]
+pub struct RustMemoryStorage {
+    /// Items in id order; reads share the lock, writes take it exclusively
+    data: Arc<std::sync::RwLock<Vec<MemoryItem>>>,
+    next_id: Arc<Mutex<u64>>,
+    /// Open cursors (cursor_id -> position index into data)
+    cursors: Arc<Mutex<HashMap<u64, usize>>>,
+    next_cursor_id: std::sync::atomic::AtomicU64,
+    tokenizer: Arc<std::sync::RwLock<TokenizerConfig>>,
+    corpus: Arc<std::sync::RwLock<CorpusStats>>,
+    /// Bytes this store has registered in `GLOBAL_MEMORY_BYTES`
+    accounted_bytes: std::sync::atomic::AtomicUsize,
+    /// Operation journal, if the store was opened with a journal_path
+    journal: Mutex<Option<JournalWriter>>,
+    /// Collapse whitespace runs and trim content on save
+    normalize_content: bool,
+    /// Score with plain TF cosine until the store holds this many items
+    min_docs_for_idf: std::sync::atomic::AtomicUsize,
+    /// Return the existing id instead of saving content that is already stored
+    dedup: bool,
+    /// Also treat items at least this TF-cosine-similar as duplicates (None = exact only)
+    dedup_threshold: Option<f64>,
+}
+
+impl RustMemoryStorage {
+    // Helper function to append a record to the journal, if one is attached
+    fn journal_append(&self, record: &JournalRecord) -> PyResult<()> {
+        let mut journal = self.journal.lock().map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to acquire lock: {}",
+                "Failed to acquire journal lock: {}",
                 e
             ))
         })?;
-        Ok(*count < self.max_recursion_depth)
+        match journal.as_mut() {
+            Some(writer) => writer.append(record),
+            None => Ok(()),
+        }
     }
 
-    /// Begin execution - returns an execution ID for tracking
-    pub fn begin_execution(&self, tool_name: &str, args: &str) -> PyResult<String> {
-        let mut count = self.execution_count.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to acquire lock: {}",
-                e
-            ))
-        })?;
+    // Helper function to build a stored item with the current tokenizer settings
+    fn build_item(tokenizer: &TokenizerConfig, id: u64, value: &str) -> MemoryItem {
+        let word_frequencies = tokenizer.compute_word_frequencies(value);
+        let term_norm = Self::calculate_term_norm(&word_frequencies);
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        MemoryItem {
+            id,
+            content: value.to_string(),
+            token_count: token_count(&word_frequencies),
+            word_frequencies,
+            created_at,
+            access_count: AccessCount::default(),
+            tfidf_norm: NormCache::default(),
+            term_norm: std::sync::OnceLock::from(term_norm),
+        }
+    }
 
-        if *count >= self.max_recursion_depth {
-            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                "Maximum recursion depth exceeded".to_string(),
-            ));
+    // Helper function to find an existing item that `item` duplicates, if dedup is on:
+    // identical content wins, otherwise the most similar item at or above the threshold
+    fn find_duplicate<'a>(&self, candidates: impl Iterator<Item = &'a MemoryItem>, item: &MemoryItem) -> Option<u64> {
+        if !self.dedup {
+            return None;
         }
 
-        *count += 1;
+        let mut best: Option<(u64, f64)> = None;
+        for existing in candidates {
+            if existing.content == item.content {
+                return Some(existing.id);
+            }
+            if let Some(threshold) = self.dedup_threshold {
+                let similarity = Self::calculate_cosine_similarity(
+                    &item.word_frequencies,
+                    item.term_norm(),
+                    &existing.word_frequencies,
+                    existing.term_norm(),
+                );
+                let better = match best {
+                    Some((_, best_similarity)) => similarity > best_similarity,
+                    None => true,
+                };
+                if similarity >= threshold && better {
+                    best = Some((existing.id, similarity));
+                }
+            }
+        }
+        best.map(|(id, _)| id)
+    }
 
-        // Update stats
-        let mut stats = self.stats.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
-        })?;
-        stats.total_executions += 1;
+    // Helper function to apply `normalize_content` (whitespace collapse and trim) to a value
+    fn normalized_content<'a>(&self, value: &'a str) -> std::borrow::Cow<'a, str> {
+        if self.normalize_content {
+            std::borrow::Cow::Owned(value.split_whitespace().collect::<Vec<_>>().join(" "))
+        } else {
+            std::borrow::Cow::Borrowed(value)
+        }
+    }
 
-        // Generate a unique execution ID
-        Ok(format!("{}:{}", tool_name, args.len()))
+    // Helper function to reserve bytes in the global budget, failing if the cap would be exceeded
+    fn reserve_global_bytes(&self, bytes: usize) -> PyResult<()> {
+        let cap = GLOBAL_MEMORY_CAP.load(std::sync::atomic::Ordering::SeqCst);
+        GLOBAL_MEMORY_BYTES
+            .fetch_update(
+                std::sync::atomic::Ordering::SeqCst,
+                std::sync::atomic::Ordering::SeqCst,
+                |total| total.checked_add(bytes).filter(|new_total| *new_total <= cap),
+            )
+            .map_err(|total| {
+                PyErr::new::<pyo3::exceptions::PyMemoryError, _>(format!(
+                    "Global memory cap of {} bytes exceeded ({} in use, {} requested)",
+                    cap, total, bytes
+                ))
+            })?;
+        self.accounted_bytes.fetch_add(bytes, std::sync::atomic::Ordering::SeqCst);
+        Ok(())
     }
 
-    /// End execution - call this after tool completes
-    pub fn end_execution(&self) -> PyResult<()> {
-        let mut count = self.execution_count.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to acquire lock: {}",
-                e
-            ))
-        })?;
+    // Helper function to replace this store's registered byte count (never fails, so
+    // bookkeeping like reindex can't be blocked by the cap)
+    fn set_accounted_bytes(&self, bytes: usize) {
+        let previous = self.accounted_bytes.swap(bytes, std::sync::atomic::Ordering::SeqCst);
+        if bytes >= previous {
+            GLOBAL_MEMORY_BYTES.fetch_add(bytes - previous, std::sync::atomic::Ordering::SeqCst);
+        } else {
+            GLOBAL_MEMORY_BYTES.fetch_sub(previous - bytes, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
 
-        if *count > 0 {
-            *count -= 1;
+    // Helper function to calculate cosine similarity between two word frequency maps (private,
+    // not exposed to Python). Callers pass both vectors' norms (see `calculate_term_norm`),
+    // and only terms in both maps contribute to the dot product, so it walks the smaller map
+    // and probes the larger one.
+    fn calculate_cosine_similarity(
+        query_freq: &HashMap<String, f64>,
+        query_norm: f64,
+        item_freq: &HashMap<String, f64>,
+        item_norm: f64,
+    ) -> f64 {
+        if query_norm == 0.0 || item_norm == 0.0 {
+            return 0.0; // No similarity if one vector is zero
         }
-        Ok(())
+
+        let (smaller, larger) = if query_freq.len() <= item_freq.len() {
+            (query_freq, item_freq)
+        } else {
+            (item_freq, query_freq)
+        };
+        let dot_product: f64 = smaller
+            .iter()
+            .filter_map(|(term, tf)| larger.get(term).map(|other_tf| tf * other_tf))
+            .sum();
+
+        dot_product / (query_norm * item_norm)
     }
 
-    /// Get cached result if available and not expired
-    pub fn get_cached(&self, tool_name: &str, args: &str) -> PyResult<Option<String>> {
-        let cache_key = format!("{}:{}", tool_name, args);
+    // Helper function to compute the L2 norm of a plain term-frequency vector
+    fn calculate_term_norm(freq: &HashMap<String, f64>) -> f64 {
+        freq.values().map(|tf| tf * tf).sum::<f64>().sqrt()
+    }
 
-        let mut cache = self.result_cache.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to acquire cache lock: {}",
-                e
-            ))
-        })?;
+    // Helper function to calculate cosine similarity with every term frequency weighted by
+    // its smoothed inverse document frequency ln(1 + N / df) over the stored corpus
+    // (private, not exposed to Python). Terms found in every item still weigh ln(2), so an
+    // exact match never scores 0; query terms no stored item contains weigh 0 since they
+    // can't match anything. Callers pass both vectors'
+    // norms (see `calculate_tfidf_norm`) so a search computes the query norm once and
+    // reuses each item's cached norm.
+    fn calculate_tfidf_similarity(
+        query_freq: &HashMap<String, f64>,
+        query_norm: f64,
+        item_freq: &HashMap<String, f64>,
+        item_norm: f64,
+        corpus: &CorpusStats,
+    ) -> f64 {
+        if query_norm == 0.0 || item_norm == 0.0 {
+            return 0.0; // No similarity if one vector is zero
+        }
 
-        if let Some(cached) = cache.get(&cache_key) {
-            // Check if cache is still valid
-            if cached.timestamp.elapsed().as_secs() < self.cache_ttl_secs {
-                let mut stats = self.stats.lock().map_err(|e| {
-                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
-                })?;
-                stats.cache_hits += 1;
-                return Ok(Some(cached.result.clone()));
+        let mut dot_product = 0.0;
+        for (term, query_tf) in query_freq {
+            if let Some(item_tf) = item_freq.get(term) {
+                let weight = Self::idf(term, corpus);
+                dot_product += query_tf * item_tf * weight * weight;
             }
-            // Entry expired - remove it from cache
-            cache.remove(&cache_key);
         }
 
-        let mut stats = self.stats.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
-        })?;
-        stats.cache_misses += 1;
-        Ok(None)
+        dot_product / (query_norm * item_norm)
     }
 
-    /// Store result in cache
-    pub fn cache_result(&self, tool_name: &str, args: &str, result: &str) -> PyResult<()> {
-        let cache_key = format!("{}:{}", tool_name, args);
+    // Helper function to get a term's smoothed inverse document frequency ln(1 + N / df),
+    // 0 for unseen terms
+    fn idf(term: &str, corpus: &CorpusStats) -> f64 {
+        match corpus.document_frequencies.get(term) {
+            Some(&df) if df > 0 => (1.0 + corpus.document_count as f64 / df as f64).ln(),
+            _ => 0.0,
+        }
+    }
 
-        let mut cache = self.result_cache.lock().map_err(|e| {
+    // Helper function to compute the L2 norm of a term-frequency vector weighted by IDF
+    fn calculate_tfidf_norm(freq: &HashMap<String, f64>, corpus: &CorpusStats) -> f64 {
+        freq.iter()
+            .map(|(term, tf)| {
+                let weight = tf * Self::idf(term, corpus);
+                weight * weight
+            })
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    // Helper function to calculate Jaccard similarity of the two term sets (private, not exposed to Python)
+    fn calculate_jaccard_similarity(query_freq: &HashMap<String, f64>, item_freq: &HashMap<String, f64>) -> f64 {
+        let shared = query_freq.keys().filter(|term| item_freq.contains_key(*term)).count();
+        let union = query_freq.len() + item_freq.len() - shared;
+        if union == 0 {
+            return 0.0;
+        }
+        shared as f64 / union as f64
+    }
+
+    // Helper function to count distinct query terms present in the item (private, not exposed to Python)
+    fn calculate_term_overlap(query_freq: &HashMap<String, f64>, item_freq: &HashMap<String, f64>) -> f64 {
+        query_freq.keys().filter(|term| item_freq.contains_key(*term)).count() as f64
+    }
+
+    // Helper function to order scored items: score descending, ties by item id
+    // (ascending = oldest first, or descending when newest_first is set)
+    fn compare_ranked(a: (f64, u64), b: (f64, u64), newest_first: bool) -> std::cmp::Ordering {
+        b.0.partial_cmp(&a.0)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| if newest_first { b.1.cmp(&a.1) } else { a.1.cmp(&b.1) })
+    }
+
+    // Helper function to compute the Okapi BM25 score of an item for a query (private,
+    // not exposed to Python). Uses the stored token count and the corpus's running
+    // average length, so no document is re-tokenized at search time.
+    fn bm25_score(item: &MemoryItem, query_freq: &HashMap<String, f64>, corpus: &CorpusStats, k1: f64, b: f64) -> f64 {
+        let n = corpus.document_count as f64;
+        let avg_len = corpus.average_document_length();
+        let length_ratio = if avg_len > 0.0 {
+            item.token_count as f64 / avg_len
+        } else {
+            0.0
+        };
+
+        query_freq
+            .keys()
+            .filter_map(|term| {
+                let tf = *item.word_frequencies.get(term)?;
+                let df = *corpus.document_frequencies.get(term)? as f64;
+                let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+                Some(idf * tf * (k1 + 1.0) / (tf + k1 * (1.0 - b + b * length_ratio)))
+            })
+            .sum()
+    }
+
+    // Helper function behind `export_vectors` and `export_vectors_sparse`: the sorted
+    // vocabulary, and per item (in id order) its nonzero tf * idf weights by term index
+    fn tfidf_vectors(&self) -> PyResult<ExportedVectors<Vec<(usize, f64)>>> {
+        let data = self.data.read().map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to acquire cache lock: {}",
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
+        let corpus = self.corpus.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire corpus lock: {}",
                 e
             ))
         })?;
 
-        // Enforce cache size limit if set
-        let max_size = self.max_cache_size.load(std::sync::atomic::Ordering::SeqCst);
-        if max_size > 0 && cache.len() >= max_size {
-            // First, remove all expired entries
-            let expired_keys: Vec<_> = cache
-                .iter()
-                .filter(|(_, v)| v.timestamp.elapsed().as_secs() >= self.cache_ttl_secs)
-                .map(|(k, _)| k.clone())
-                .collect();
-            for key in expired_keys {
-                cache.remove(&key);
-            }
+        let mut vocabulary: Vec<String> = corpus.document_frequencies.keys().cloned().collect();
+        vocabulary.sort();
+        let index: HashMap<&str, usize> = vocabulary
+            .iter()
+            .enumerate()
+            .map(|(i, term)| (term.as_str(), i))
+            .collect();
 
-            // If still over limit, remove oldest entries (first N entries in hashmap order)
-            // This is a simple eviction strategy; a true LRU would require ordered structure
-            if cache.len() >= max_size {
-                let to_remove = cache.len() - max_size + 1;
-                let keys_to_remove: Vec<_> = cache.keys().take(to_remove).cloned().collect();
-                for key in keys_to_remove {
-                    cache.remove(&key);
-                }
-            }
-        }
+        // Below `min_docs_for_idf` searches use plain TF, so the export does too
+        let plain = corpus.document_count < self.min_docs_for_idf.load(std::sync::atomic::Ordering::SeqCst);
+        let vectors = data
+            .iter()
+            .map(|item| {
+                let mut weights: Vec<(usize, f64)> = item
+                    .word_frequencies
+                    .iter()
+                    .filter_map(|(term, tf)| {
+                        let weight = if plain { *tf } else { tf * Self::idf(term, &corpus) };
+                        (weight != 0.0).then(|| (index[term.as_str()], weight))
+                    })
+                    .collect();
+                weights.sort_by_key(|(i, _)| *i);
+                (item.id, weights)
+            })
+            .collect();
 
-        cache.insert(
-            cache_key,
-            CachedResult {
-                result: result.to_string(),
-                timestamp: std::time::Instant::now(),
-            },
-        );
+        Ok((vocabulary, vectors))
+    }
 
-        Ok(())
+    // Helper function to check whether an item contains any query term. Both similarity
+    // measures are exactly 0 without a shared term, so scoring skips those items (and the
+    // item norm they'd need) after this cheap probe of the query's few terms.
+    fn shares_term(query_freq: &HashMap<String, f64>, item_freq: &HashMap<String, f64>) -> bool {
+        query_freq.keys().any(|term| item_freq.contains_key(term))
     }
 
-    /// Get current cache size
-    pub fn get_cache_size(&self) -> PyResult<usize> {
-        let cache = self.result_cache.lock().map_err(|e| {
+    // Helper function to count an access of each listed item, for callers that rank
+    // without recording accesses and keep only some of the results
+    fn record_accesses(&self, ids: &std::collections::HashSet<u64>) -> PyResult<()> {
+        let data = self.data.read().map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to acquire cache lock: {}",
+                "Failed to acquire lock: {}",
                 e
             ))
         })?;
-        Ok(cache.len())
+        for item in data.iter().filter(|item| ids.contains(&item.id)) {
+            item.access_count.increment();
+        }
+        Ok(())
     }
 
-    /// Clear the result cache
-    pub fn clear_cache(&self) -> PyResult<usize> {
-        let mut cache = self.result_cache.lock().map_err(|e| {
+    // Helper function to score every item against a query (private, not exposed to Python).
+    // Items sharing no query term are skipped. `min_score` isn't used to prune further:
+    // with cached norms the exact score is one pass over the query terms, which is what any
+    // useful upper bound would cost too, so callers filter on the finished scores.
+    fn score_items(&self, items: &[MemoryItem], query_freq: &HashMap<String, f64>) -> PyResult<Vec<f64>> {
+        let corpus = self.corpus.read().map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to acquire cache lock: {}",
+                "Failed to acquire corpus lock: {}",
                 e
             ))
         })?;
 
-        let count = cache.len();
-        cache.clear();
-        Ok(count)
+        if corpus.document_count < self.min_docs_for_idf.load(std::sync::atomic::Ordering::SeqCst) {
+            let query_norm = Self::calculate_term_norm(query_freq);
+            return Ok(items
+                .iter()
+                .map(|item| {
+                    if !Self::shares_term(query_freq, &item.word_frequencies) {
+                        return 0.0;
+                    }
+                    Self::calculate_cosine_similarity(query_freq, query_norm, &item.word_frequencies, item.term_norm())
+                })
+                .collect());
+        }
+
+        let query_norm = Self::calculate_tfidf_norm(query_freq, &corpus);
+        Ok(items
+            .iter()
+            .map(|item| {
+                if !Self::shares_term(query_freq, &item.word_frequencies) {
+                    return 0.0;
+                }
+                Self::calculate_tfidf_similarity(
+                    query_freq,
+                    query_norm,
+                    &item.word_frequencies,
+                    item.tfidf_norm(&corpus),
+                    &corpus,
+                )
+            })
+            .collect())
     }
 
-    /// Remove all expired entries from the cache
-    pub fn cleanup_expired(&self) -> PyResult<usize> {
-        let mut cache = self.result_cache.lock().map_err(|e| {
+    // Helper function behind `search` and `search_full`: rank every item against the query
+    // and return the best `limit` as (id, content, score), recording an access for each
+    // when `record_access` is set
+    #[allow(clippy::too_many_arguments)]
+    fn ranked_search(
+        &self,
+        query: &str,
+        limit: usize,
+        newest_first: bool,
+        length_penalty: f64,
+        min_tokens: usize,
+        min_score: f64,
+        record_access: bool,
+    ) -> PyResult<Vec<(u64, String, f64)>> {
+        let data = self.data.read().map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to acquire cache lock: {}",
+                "Failed to acquire lock: {}",
                 e
             ))
         })?;
 
-        let expired_keys: Vec<_> = cache
-            .iter()
-            .filter(|(_, v)| v.timestamp.elapsed().as_secs() >= self.cache_ttl_secs)
-            .map(|(k, _)| k.clone())
-            .collect();
-
-        for key in &expired_keys {
-            cache.remove(key);
-        }
-
-        Ok(expired_keys.len())
-    }
-
-    /// Get execution statistics
-    pub fn get_stats(&self) -> PyResult<HashMap<String, usize>> {
-        let stats = self.stats.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        let tokenizer = self.tokenizer.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire tokenizer lock: {}",
+                e
+            ))
         })?;
 
-        let mut result = HashMap::new();
-        result.insert("total_executions".to_string(), stats.total_executions);
-        result.insert("cache_hits".to_string(), stats.cache_hits);
-        result.insert("cache_misses".to_string(), stats.cache_misses);
-        result.insert("validation_failures".to_string(), stats.validation_failures);
+        // Compute query word frequencies
+        let query_frequencies = tokenizer.compute_word_frequencies(query);
 
-        // Calculate cache hit rate
-        let total_cache_lookups = stats.cache_hits + stats.cache_misses;
-        if total_cache_lookups > 0 {
-            result.insert(
-                "cache_hit_rate_percent".to_string(),
-                (stats.cache_hits * 100) / total_cache_lookups,
-            );
-        }
+        // Calculate similarity scores for each item (by index), down-weighting very short items
+        let mut scored_results: Vec<(usize, f64)> = data
+            .iter()
+            .zip(self.score_items(&data, &query_frequencies)?)
+            .enumerate()
+            .map(|(index, (item, score))| {
+                if length_penalty > 0.0 && item.token_count < min_tokens {
+                    let ratio = item.token_count as f64 / min_tokens as f64;
+                    (index, score * ratio.powf(length_penalty))
+                } else {
+                    (index, score)
+                }
+            })
+            .filter(|(_, score)| *score >= min_score)
+            .collect();
 
-        Ok(result)
-    }
+        // Sort by similarity score (descending), breaking ties by id
+        scored_results.sort_by(|a, b| {
+            Self::compare_ranked((a.1, data[a.0].id), (b.1, data[b.0].id), newest_first)
+        });
 
-    /// Batch validate multiple tool argument sets
-    pub fn batch_validate(&self, args_list: Vec<String>) -> PyResult<Vec<bool>> {
-        args_list
-            .iter()
-            .map(|args| {
-                serde_json::from_str::<serde_json::Value>(args)
-                    .map(|_| true)
-                    .unwrap_or(false)
+        // Take top results up to limit
+        Ok(scored_results
+            .into_iter()
+            .take(limit)
+            .map(|(index, score)| {
+                let item = &data[index];
+                if record_access {
+                    item.access_count.increment();
+                }
+                (item.id, item.content.clone(), score)
             })
-            .map(Ok)
-            .collect()
+            .collect())
     }
 }
 
-/// A message structure for serialization
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[pyclass]
-pub struct AgentMessage {
-    #[pyo3(get, set)]
-    pub id: String,
-    #[pyo3(get, set)]
-    pub sender: String,
-    #[pyo3(get, set)]
-    pub recipient: String,
-    #[pyo3(get, set)]
-    pub content: String,
-    #[pyo3(get, set)]
-    pub timestamp: u64,
+impl Default for RustMemoryStorage {
+    fn default() -> Self {
+        Self::new(1, None, false, false, None, false, 0, false, None, false).expect("a store without a journal cannot fail to open")
+    }
+}
+
+impl Drop for RustMemoryStorage {
+    fn drop(&mut self) {
+        // Release this store's share of the process-wide memory budget
+        self.set_accounted_bytes(0);
+    }
 }
 
 #[pymethods]
-impl AgentMessage {
+impl RustMemoryStorage {
+    /// Create an empty storage.
+    ///
+    /// `min_token_len` drops tokens shorter than that many characters from both stored
+    /// and query vectors (default 1 keeps every token). It changes the stored word
+    /// frequencies, so keep it fixed for the lifetime of a store.
+    ///
+    /// With `journal_path`, every `save`, `delete` and `clear` is first appended to that file as one
+    /// JSON line, so the store can be rebuilt after a crash with `replay_journal`. Records
+    /// are written straight to the OS but only fsynced when `journal_fsync` is true; without
+    /// it a power loss (not a process crash) can drop the most recent records. The journal
+    /// grows with every write; call `compact_journal` to rewrite it as the current contents.
+    ///
+    /// With `normalize_content`, `save` collapses every run of whitespace to a single space
+    /// and trims both ends before storing, so `get_all` returns the normalized text. This is
+    /// irreversible: the original spacing is not kept anywhere, including the journal.
+    ///
+    /// `stopwords` lists words dropped during tokenization of both saved items and queries,
+    /// matched case-insensitively; `english_stopwords=True` adds a built-in list of common
+    /// English function words. Like `min_token_len`, this shapes the stored frequencies.
+    ///
+    /// `min_docs_for_idf` guards against noisy IDF weights in tiny corpora: while the store
+    /// holds fewer items than this, searches and `central_memories` score by plain
+    /// term-frequency cosine, and once it reaches that size they switch to TF-IDF, so
+    /// rankings can shift when the threshold is crossed (in either direction, e.g. after
+    /// deletes). The default of 0 always uses TF-IDF. See also `set_min_docs_for_idf`.
+    ///
+    /// With `dedup`, saving content identical to a stored item (after any normalization)
+    /// stores nothing and returns the existing item's id. `dedup_threshold` (which implies
+    /// `dedup`) also catches near-duplicates: the most similar stored item whose plain
+    /// term-frequency cosine similarity is at least the threshold. Either check scans every
+    /// stored item, so saves become O(n).
+    ///
+    /// `cjk_mode` makes Chinese, Japanese and Korean text searchable despite having no
+    /// spaces between words: each run of CJK characters is indexed as its overlapping
+    /// character bigrams ("東京都" -> "東京", "京都"), CJK punctuation separates tokens,
+    /// and other text is tokenized as usual, so mixed-script text is handled segment by
+    /// segment. Bigrams are a lightweight heuristic, not word segmentation: they over-match
+    /// across word boundaries, and they are exempt from `min_token_len`. Only a lone CJK
+    /// character is indexed on its own, so a one-character query like "京" matches items
+    /// where that character stands alone, not words containing it such as "東京". Like the
+    /// other tokenizer options it applies to both saved items and queries.
     #[new]
-    pub fn new(id: &str, sender: &str, recipient: &str, content: &str, timestamp: u64) -> Self {
-        AgentMessage {
-            id: id.to_string(),
-            sender: sender.to_string(),
-            recipient: recipient.to_string(),
-            content: content.to_string(),
-            timestamp,
+    #[pyo3(signature = (min_token_len=1, journal_path=None, journal_fsync=false, normalize_content=false, stopwords=None, english_stopwords=false, min_docs_for_idf=0, dedup=false, dedup_threshold=None, cjk_mode=false))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        min_token_len: usize,
+        journal_path: Option<&str>,
+        journal_fsync: bool,
+        normalize_content: bool,
+        stopwords: Option<Vec<String>>,
+        english_stopwords: bool,
+        min_docs_for_idf: usize,
+        dedup: bool,
+        dedup_threshold: Option<f64>,
+        cjk_mode: bool,
+    ) -> PyResult<Self> {
+        if let Some(threshold) = dedup_threshold {
+            if !(threshold > 0.0 && threshold <= 1.0) {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "dedup_threshold must be in (0.0, 1.0], got {}",
+                    threshold
+                )));
+            }
         }
+
+        let journal = journal_path
+            .map(|path| JournalWriter::open(path, journal_fsync))
+            .transpose()?;
+
+        Ok(RustMemoryStorage {
+            data: Arc::new(std::sync::RwLock::new(Vec::new())),
+            next_id: Arc::new(Mutex::new(0)),
+            cursors: Arc::new(Mutex::new(HashMap::new())),
+            next_cursor_id: std::sync::atomic::AtomicU64::new(0),
+            tokenizer: Arc::new(std::sync::RwLock::new(TokenizerConfig {
+                min_token_len,
+                stopwords: TokenizerConfig::build_stopwords(stopwords, english_stopwords),
+                cjk_mode,
+                ..TokenizerConfig::default()
+            })),
+            corpus: Arc::new(std::sync::RwLock::new(CorpusStats::default())),
+            accounted_bytes: std::sync::atomic::AtomicUsize::new(0),
+            journal: Mutex::new(journal),
+            normalize_content,
+            min_docs_for_idf: std::sync::atomic::AtomicUsize::new(min_docs_for_idf),
+            dedup: dedup || dedup_threshold.is_some(),
+            dedup_threshold,
+        })
     }
 
-    pub fn to_json(&self) -> PyResult<String> {
-        serde_json::to_string(self).map_err(|e| {
+    /// Store a value and return its id.
+    ///
+    /// Ids start at 0 and increase in save order; an id is never handed out twice, even
+    /// after the item holding it is deleted.
+    pub fn save(&self, value: &str) -> PyResult<u64> {
+        let mut data = self.data.write().map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to serialize to JSON: {}",
+                "Failed to acquire lock: {}",
                 e
             ))
-        })
-    }
+        })?;
 
-    #[staticmethod]
-    pub fn from_json(json_str: &str) -> PyResult<AgentMessage> {
-        serde_json::from_str(json_str).map_err(|e| {
+        let mut next_id = self.next_id.lock().map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to deserialize from JSON: {}",
+                "Failed to acquire id lock: {}",
                 e
             ))
-        })
-    }
-}
-
-/// Task state for tracking execution
-#[derive(Debug, Clone, PartialEq)]
-enum TaskState {
-    Pending,
-    Running,
-    Completed,
-    Failed,
-}
+        })?;
 
-/// A task with dependencies and state tracking
-#[derive(Debug, Clone)]
-struct TaskInfo {
-    dependencies: Vec<String>,
-    state: TaskState,
-    result: Option<String>,
-    error: Option<String>,
-}
+        let tokenizer = self.tokenizer.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire tokenizer lock: {}",
+                e
+            ))
+        })?;
 
-/// A concurrent task executor with dependency tracking
-#[pyclass]
-pub struct RustTaskExecutor {
-    // Use Box wrapped in Option - allows us to take ownership in Drop
-    // PyO3 manages the Python object lifetime
-    runtime: Option<Box<tokio::runtime::Runtime>>,
-    tasks: Arc<Mutex<HashMap<String, TaskInfo>>>,
-    stats: Arc<Mutex<TaskExecutionStats>>,
-}
+        let mut corpus = self.corpus.write().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire corpus lock: {}",
+                e
+            ))
+        })?;
 
-impl Drop for RustTaskExecutor {
-    fn drop(&mut self) {
-        // Explicitly shutdown the runtime to clean up worker threads
-        // This prevents memory leaks when many executors are created
-        // By taking the Box and letting it go out of scope, the Runtime's destructor
-        // will properly shutdown all worker threads
-        if let Some(runtime) = self.runtime.take() {
-            drop(runtime);
-        }
-    }
-}
+        let value = self.normalized_content(value);
 
-#[derive(Debug, Clone, Default)]
-struct TaskExecutionStats {
-    tasks_scheduled: usize,
-    tasks_completed: usize,
-    tasks_failed: usize,
-    total_execution_time_ms: u64,
-}
+        // Create word frequency map for TF-IDF
+        let id = *next_id;
+        let item = Self::build_item(&tokenizer, id, &value);
+        if let Some(existing) = self.find_duplicate(data.iter(), &item) {
+            return Ok(existing);
+        }
 
-#[pymethods]
-impl RustTaskExecutor {
-    #[new]
-    pub fn new() -> PyResult<Self> {
-        let runtime = tokio::runtime::Builder::new_multi_thread()
-            .enable_all()
-            .build()
-            .map_err(|e| {
-                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Failed to create Tokio runtime: {}",
-                    e
-                ))
-            })?;
+        self.reserve_global_bytes(item.estimated_bytes())?;
+        if let Err(e) = self.journal_append(&JournalRecord::Save {
+            id,
+            content: value.to_string(),
+            created_at: Some(item.created_at),
+        }) {
+            self.set_accounted_bytes(self.memory_usage_bytes() - item.estimated_bytes());
+            return Err(e);
+        }
+        corpus.add_document(&item.word_frequencies);
+        data.push(item);
+        *next_id += 1;
 
-        Ok(RustTaskExecutor {
-            runtime: Some(Box::new(runtime)),
-            tasks: Arc::new(Mutex::new(HashMap::new())),
-            stats: Arc::new(Mutex::new(TaskExecutionStats::default())),
-        })
+        Ok(id)
     }
 
-    /// Register a task with optional dependencies
-    pub fn register_task(&self, task_id: &str, dependencies: Vec<String>) -> PyResult<()> {
-        let mut tasks = self.tasks.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+    /// Store several values under one acquisition of the store's locks, returning their
+    /// ids in input order. New ids are contiguous (with `dedup`, a duplicate gets the id
+    /// of the item it duplicates), and the whole batch is checked against the global
+    /// memory cap up front, so either every item fits or none is saved.
+    ///
+    /// With a journal, each item is still journaled as its own `save` record. If a journal
+    /// write fails partway, the items journaled before it are kept (so the store matches
+    /// what a replay would rebuild) and the error is raised.
+    pub fn save_batch(&self, values: Vec<String>) -> PyResult<Vec<u64>> {
+        let mut data = self.data.write().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
         })?;
 
-        tasks.insert(
-            task_id.to_string(),
-            TaskInfo {
-                dependencies,
-                state: TaskState::Pending,
-                result: None,
-                error: None,
-            },
-        );
-
-        let mut stats = self.stats.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        let mut next_id = self.next_id.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire id lock: {}",
+                e
+            ))
         })?;
-        stats.tasks_scheduled += 1;
-
-        Ok(())
-    }
 
-    /// Check if a task's dependencies are all completed
-    pub fn can_execute(&self, task_id: &str) -> PyResult<bool> {
-        let tasks = self.tasks.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        let tokenizer = self.tokenizer.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire tokenizer lock: {}",
+                e
+            ))
         })?;
 
-        if let Some(task) = tasks.get(task_id) {
-            for dep_id in &task.dependencies {
-                if let Some(dep_task) = tasks.get(dep_id) {
-                    if dep_task.state != TaskState::Completed {
-                        return Ok(false);
-                    }
-                } else {
-                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
-                        "Dependency task '{}' not found",
-                        dep_id
-                    )));
-                }
-            }
-            Ok(true)
-        } else {
-            Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
-                "Task '{}' not found",
-                task_id
-            )))
-        }
-    }
-
-    /// Get all tasks that are ready to execute (dependencies satisfied)
-    pub fn get_ready_tasks(&self) -> PyResult<Vec<String>> {
-        let tasks = self.tasks.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        let mut corpus = self.corpus.write().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire corpus lock: {}",
+                e
+            ))
         })?;
 
-        let mut ready = Vec::new();
-
-        for (task_id, task) in tasks.iter() {
-            if task.state != TaskState::Pending {
-                continue;
-            }
-
-            let mut deps_satisfied = true;
-            for dep_id in &task.dependencies {
-                if let Some(dep_task) = tasks.get(dep_id) {
-                    if dep_task.state != TaskState::Completed {
-                        deps_satisfied = false;
-                        break;
-                    }
-                } else {
-                    deps_satisfied = false;
-                    break;
+        // Duplicates (with dedup on) resolve to stored items or to earlier values in the batch
+        let mut ids = Vec::with_capacity(values.len());
+        let mut items: Vec<MemoryItem> = Vec::new();
+        for value in &values {
+            let id = *next_id + items.len() as u64;
+            let item = Self::build_item(&tokenizer, id, &self.normalized_content(value));
+            match self.find_duplicate(data.iter().chain(items.iter()), &item) {
+                Some(existing) => ids.push(existing),
+                None => {
+                    ids.push(id);
+                    items.push(item);
                 }
             }
-
-            if deps_satisfied {
-                ready.push(task_id.clone());
+        }
+        let mut pending_bytes: usize = items.iter().map(MemoryItem::estimated_bytes).sum();
+        self.reserve_global_bytes(pending_bytes)?;
+
+        for item in items {
+            if let Err(e) = self.journal_append(&JournalRecord::Save {
+                id: item.id,
+                content: item.content.clone(),
+                created_at: Some(item.created_at),
+            }) {
+                self.set_accounted_bytes(self.memory_usage_bytes() - pending_bytes);
+                return Err(e);
             }
+            pending_bytes -= item.estimated_bytes();
+            corpus.add_document(&item.word_frequencies);
+            data.push(item);
+            *next_id += 1;
         }
 
-        Ok(ready)
+        Ok(ids)
     }
 
-    /// Mark a task as started
-    pub fn mark_started(&self, task_id: &str) -> PyResult<()> {
-        let mut tasks = self.tasks.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+    pub fn get_all(&self) -> PyResult<Vec<String>> {
+        let data = self.data.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
         })?;
-
-        if let Some(task) = tasks.get_mut(task_id) {
-            task.state = TaskState::Running;
-            Ok(())
-        } else {
-            Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
-                "Task '{}' not found",
-                task_id
-            )))
-        }
+        Ok(data.iter().map(|item| item.content.clone()).collect())
     }
 
-    /// Mark a task as completed with a result
-    pub fn mark_completed(&self, task_id: &str, result: &str) -> PyResult<()> {
-        let mut tasks = self.tasks.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+    /// Number of stored items, without copying any content
+    pub fn len(&self) -> PyResult<usize> {
+        let data = self.data.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
         })?;
+        Ok(data.len())
+    }
 
-        if let Some(task) = tasks.get_mut(task_id) {
-            task.state = TaskState::Completed;
-            task.result = Some(result.to_string());
-
-            let mut stats = self.stats.lock().map_err(|e| {
-                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
-            })?;
-            stats.tasks_completed += 1;
-
-            Ok(())
-        } else {
-            Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
-                "Task '{}' not found",
-                task_id
-            )))
-        }
+    /// Whether the store holds no items
+    pub fn is_empty(&self) -> PyResult<bool> {
+        Ok(self.len()? == 0)
     }
 
-    /// Mark a task as failed with an error message
-    pub fn mark_failed(&self, task_id: &str, error: &str) -> PyResult<()> {
-        let mut tasks = self.tasks.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+    /// Remove the item with the given id, returning whether it existed.
+    ///
+    /// Ids are never reused: later saves keep counting up from the highest id handed out,
+    /// deleted or not. Open cursors keep their place, so a deletion never makes `fetch`
+    /// skip an item.
+    pub fn delete(&self, id: u64) -> PyResult<bool> {
+        let mut data = self.data.write().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
         })?;
 
-        if let Some(task) = tasks.get_mut(task_id) {
-            task.state = TaskState::Failed;
-            task.error = Some(error.to_string());
+        let mut corpus = self.corpus.write().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire corpus lock: {}",
+                e
+            ))
+        })?;
 
-            let mut stats = self.stats.lock().map_err(|e| {
-                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
-            })?;
-            stats.tasks_failed += 1;
+        // Items are kept in id order
+        let index = match data.binary_search_by_key(&id, |item| item.id) {
+            Ok(index) => index,
+            Err(_) => return Ok(false),
+        };
 
-            Ok(())
-        } else {
-            Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
-                "Task '{}' not found",
-                task_id
-            )))
-        }
-    }
+        self.journal_append(&JournalRecord::Delete { id })?;
+        let item = data.remove(index);
+        corpus.remove_document(&item.word_frequencies);
+        self.set_accounted_bytes(self.memory_usage_bytes() - item.estimated_bytes());
 
-    /// Get the result of a completed task
-    pub fn get_result(&self, task_id: &str) -> PyResult<Option<String>> {
-        let tasks = self.tasks.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        let mut cursors = self.cursors.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cursor lock: {}",
+                e
+            ))
         })?;
-
-        if let Some(task) = tasks.get(task_id) {
-            Ok(task.result.clone())
-        } else {
-            Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
-                "Task '{}' not found",
-                task_id
-            )))
+        for position in cursors.values_mut() {
+            if *position > index {
+                *position -= 1;
+            }
         }
+
+        Ok(true)
     }
 
-    /// Get topological sort order for task execution
-    pub fn get_execution_order(&self) -> PyResult<Vec<String>> {
-        let tasks = self.tasks.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
-        })?;
+    /// Search using a custom Python scoring function.
+    ///
+    /// `scorer(query_freqs: dict, item_freqs: dict) -> float` is called once per stored
+    /// item with the term-frequency maps, and results are ranked by its return value
+    /// (ties by id). This costs a Python call per item, so it is meant for prototyping
+    /// metrics rather than hot paths. An exception raised by the scorer aborts the search.
+    /// The store is not locked while the scorer runs, so it may safely use the store.
+    pub fn search_custom(
+        &self,
+        py: Python<'_>,
+        query: &str,
+        limit: usize,
+        scorer: PyObject,
+    ) -> PyResult<Vec<(String, f64)>> {
+        let (query_frequencies, items) = {
+            let data = self.data.read().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire lock: {}",
+                    e
+                ))
+            })?;
 
-        // Kahn's algorithm for topological sort
-        let mut in_degree: HashMap<String, usize> = HashMap::new();
-        let mut adj_list: HashMap<String, Vec<String>> = HashMap::new();
+            let tokenizer = self.tokenizer.read().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire tokenizer lock: {}",
+                    e
+                ))
+            })?;
 
-        // Initialize
-        for (task_id, task) in tasks.iter() {
-            in_degree.entry(task_id.clone()).or_insert(0);
-            adj_list.entry(task_id.clone()).or_insert_with(Vec::new);
+            (tokenizer.compute_word_frequencies(query), data.clone())
+        };
 
-            for dep_id in &task.dependencies {
-                *in_degree.entry(task_id.clone()).or_insert(0) += 1;
-                adj_list
-                    .entry(dep_id.clone())
-                    .or_insert_with(Vec::new)
-                    .push(task_id.clone());
-            }
+        let query_dict = query_frequencies.into_pyobject(py)?;
+        let mut scored_results = Vec::with_capacity(items.len());
+        for item in &items {
+            let score: f64 = scorer
+                .call1(py, (&query_dict, &item.word_frequencies))?
+                .extract(py)?;
+            scored_results.push((item, score));
         }
 
-        // Find all nodes with no incoming edges
-        let mut queue: Vec<String> = in_degree
-            .iter()
-            .filter(|(_, &deg)| deg == 0)
-            .map(|(id, _)| id.clone())
-            .collect();
+        scored_results.sort_by(|a, b| Self::compare_ranked((a.1, a.0.id), (b.1, b.0.id), false));
 
-        let mut result = Vec::new();
+        Ok(scored_results
+            .into_iter()
+            .take(limit)
+            .map(|(item, score)| (item.content.clone(), score))
+            .collect())
+    }
 
-        while let Some(node) = queue.pop() {
-            result.push(node.clone());
+    /// Stream ranked search results to `callback(content, score)` instead of building a list.
+    ///
+    /// Every item is scored as in `search` and delivered best first (ties by id), so
+    /// only (score, id) pairs are held in memory rather than every result's content. The
+    /// callback runs as results are produced, so keep it lightweight; returning `False`
+    /// stops the stream early. No lock is held while it runs, so it may use the store;
+    /// items removed meanwhile are skipped. Returns the number of results delivered.
+    pub fn search_streaming(&self, py: Python<'_>, query: &str, callback: PyObject) -> PyResult<usize> {
+        let mut ranked: Vec<(f64, u64)> = {
+            let data = self.data.read().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire lock: {}",
+                    e
+                ))
+            })?;
 
-            if let Some(neighbors) = adj_list.get(&node) {
-                for neighbor in neighbors {
-                    if let Some(deg) = in_degree.get_mut(neighbor) {
-                        *deg -= 1;
-                        if *deg == 0 {
-                            queue.push(neighbor.clone());
-                        }
-                    }
+            let tokenizer = self.tokenizer.read().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire tokenizer lock: {}",
+                    e
+                ))
+            })?;
+
+            let query_frequencies = tokenizer.compute_word_frequencies(query);
+            self.score_items(&data, &query_frequencies)?
+                .into_iter()
+                .zip(data.iter().map(|item| item.id))
+                .collect()
+        };
+        ranked.sort_by(|a, b| Self::compare_ranked(*a, *b, false));
+
+        let mut delivered = 0;
+        for (score, id) in ranked {
+            // Items are stored in id order, so each lookup is a binary search
+            let content = {
+                let data = self.data.read().map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                        "Failed to acquire lock: {}",
+                        e
+                    ))
+                })?;
+                match data.binary_search_by_key(&id, |item| item.id) {
+                    Ok(index) => data[index].content.clone(),
+                    Err(_) => continue,
                 }
+            };
+
+            let keep_going = callback.call1(py, (content, score))?;
+            delivered += 1;
+            if matches!(keep_going.extract::<bool>(py), Ok(false)) {
+                break;
             }
         }
 
-        // Check for cycles
-        if result.len() != tasks.len() {
+        Ok(delivered)
+    }
+
+    /// Search blending relevance with recency.
+    ///
+    /// Cosine similarity is normalized by the best match and recency is min-max normalized
+    /// over item ages relative to `now_secs` (newest = 1.0, oldest = 0.0). The combined score is
+    /// `(1 - recency_weight) * similarity + recency_weight * recency`, so a weight of 0.0
+    /// ranks purely by similarity. Returns `(content, combined_score)` pairs.
+    pub fn search_with_recency(
+        &self,
+        query: &str,
+        limit: usize,
+        recency_weight: f64,
+        now_secs: u64,
+    ) -> PyResult<Vec<(String, f64)>> {
+        if !(0.0..=1.0).contains(&recency_weight) {
             return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-                "Circular dependency detected in tasks",
+                "recency_weight must be between 0.0 and 1.0",
             ));
         }
 
-        Ok(result)
+        let data = self.data.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
+
+        let tokenizer = self.tokenizer.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire tokenizer lock: {}",
+                e
+            ))
+        })?;
+
+        let query_frequencies = tokenizer.compute_word_frequencies(query);
+        let similarities = self.score_items(&data, &query_frequencies)?;
+
+        let max_similarity = similarities.iter().cloned().fold(0.0, f64::max);
+        let ages: Vec<u64> = data
+            .iter()
+            .map(|item| now_secs.saturating_sub(item.created_at))
+            .collect();
+        let min_age = ages.iter().cloned().min().unwrap_or(0);
+        let max_age = ages.iter().cloned().max().unwrap_or(0);
+
+        let mut scored_results: Vec<(&MemoryItem, f64)> = data
+            .iter()
+            .zip(similarities)
+            .zip(ages)
+            .map(|((item, similarity), age)| {
+                let similarity = if max_similarity > 0.0 {
+                    similarity / max_similarity
+                } else {
+                    0.0
+                };
+                let recency = if max_age > min_age {
+                    (max_age - age) as f64 / (max_age - min_age) as f64
+                } else {
+                    1.0
+                };
+                let blended = (1.0 - recency_weight) * similarity + recency_weight * recency;
+                (item, blended)
+            })
+            .collect();
+
+        scored_results.sort_by(|a, b| Self::compare_ranked((a.1, a.0.id), (b.1, b.0.id), false));
+
+        Ok(scored_results
+            .into_iter()
+            .take(limit)
+            .map(|(item, score)| (item.content.clone(), score))
+            .collect())
     }
 
-    /// Execute multiple independent tasks concurrently and aggregate results
-    pub fn execute_concurrent_tasks(&self, tasks: Vec<String>) -> PyResult<Vec<String>> {
-        let runtime = self.runtime.as_ref().expect("Runtime not initialized");
-        let start_time = std::time::Instant::now();
+    /// Search blending relevance with how often each item has been recalled.
+    ///
+    /// Similarity (the same TF-IDF score `search` uses) is normalized by the best match, and
+    /// each item's access count by the highest count in the store. The combined score is
+    /// `(1 - activation_weight) * similarity + activation_weight * activation`, so a weight
+    /// of 0.0 ranks exactly like a plain `search`. Returns `(content, combined_score)` pairs.
+    ///
+    /// Like `search`, this increments the access count of every returned item, so results
+    /// feed back into later rankings. Pass `record_access=False` for a read-only query that
+    /// leaves the counters untouched.
+    #[pyo3(signature = (query, limit, activation_weight, record_access=true))]
+    pub fn search_with_activation(
+        &self,
+        query: &str,
+        limit: usize,
+        activation_weight: f64,
+        record_access: bool,
+    ) -> PyResult<Vec<(String, f64)>> {
+        if !(0.0..=1.0).contains(&activation_weight) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "activation_weight must be between 0.0 and 1.0",
+            ));
+        }
 
-        let results: Result<Vec<String>, PyErr> = Python::with_gil(|py| {
-            py.allow_threads(|| {
-                runtime.block_on(async {
-                    let mut handles = Vec::new();
+        let data = self.data.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
 
-                    for task in tasks {
-                        let task_str = task.clone();
-                        let handle = tokio::spawn(async move {
-                            // Return the task ID - actual execution happens in Python
-                            task_str
-                        });
-                        handles.push(handle);
-                    }
+        let tokenizer = self.tokenizer.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire tokenizer lock: {}",
+                e
+            ))
+        })?;
 
-                    let mut results = Vec::new();
-                    for handle in handles {
-                        match handle.await {
-                            Ok(result) => results.push(result),
-                            Err(e) => {
-                                return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                                    format!("Task execution failed: {}", e),
-                                ))
-                            }
-                        }
-                    }
+        let query_frequencies = tokenizer.compute_word_frequencies(query);
+        let similarities = self.score_items(&data, &query_frequencies)?;
 
-                    Ok(results)
-                })
+        let max_similarity = similarities.iter().cloned().fold(0.0, f64::max);
+        let max_access = data.iter().map(|item| item.access_count.get()).max().unwrap_or(0);
+
+        let mut scored_results: Vec<(usize, f64)> = data
+            .iter()
+            .zip(similarities)
+            .enumerate()
+            .map(|(index, (item, similarity))| {
+                let similarity = if max_similarity > 0.0 {
+                    similarity / max_similarity
+                } else {
+                    0.0
+                };
+                let activation = if max_access > 0 {
+                    item.access_count.get() as f64 / max_access as f64
+                } else {
+                    0.0
+                };
+                let blended = (1.0 - activation_weight) * similarity + activation_weight * activation;
+                (index, blended)
             })
-        });
+            .collect();
 
-        // Update stats
-        let elapsed_ms = start_time.elapsed().as_millis() as u64;
-        if let Ok(mut stats) = self.stats.lock() {
-            stats.total_execution_time_ms += elapsed_ms;
-        }
+        scored_results.sort_by(|a, b| Self::compare_ranked((a.1, data[a.0].id), (b.1, data[b.0].id), false));
 
-        results
+        Ok(scored_results
+            .into_iter()
+            .take(limit)
+            .map(|(index, score)| {
+                if record_access {
+                    data[index].access_count.increment();
+                }
+                (data[index].content.clone(), score)
+            })
+            .collect())
     }
 
-    /// Get execution statistics
-    pub fn get_stats(&self) -> PyResult<HashMap<String, usize>> {
-        let stats = self.stats.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+    /// Search using Okapi BM25 instead of cosine similarity.
+    ///
+    /// Each query term contributes `idf * tf * (k1 + 1) / (tf + k1 * (1 - b + b * len / avg_len))`
+    /// with `idf = ln(1 + (N - df + 0.5) / (df + 0.5))`. Item lengths and the corpus average
+    /// are maintained at save time, so scoring never re-tokenizes stored items. Only items
+    /// matching at least one query term are returned, as `(content, score)` best first.
+    #[pyo3(signature = (query, limit, k1=1.2, b=0.75))]
+    pub fn search_bm25(&self, query: &str, limit: usize, k1: f64, b: f64) -> PyResult<Vec<(String, f64)>> {
+        let data = self.data.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
         })?;
 
-        let mut result = HashMap::new();
-        result.insert("tasks_scheduled".to_string(), stats.tasks_scheduled);
-        result.insert("tasks_completed".to_string(), stats.tasks_completed);
-        result.insert("tasks_failed".to_string(), stats.tasks_failed);
-        result.insert(
-            "total_execution_time_ms".to_string(),
-            stats.total_execution_time_ms as usize,
-        );
+        let tokenizer = self.tokenizer.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire tokenizer lock: {}",
+                e
+            ))
+        })?;
 
-        Ok(result)
+        let query_frequencies = tokenizer.compute_word_frequencies(query);
+
+        let corpus = self.corpus.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire corpus lock: {}",
+                e
+            ))
+        })?;
+
+        let mut scored: Vec<(&MemoryItem, f64)> = data
+            .iter()
+            .map(|item| (item, Self::bm25_score(item, &query_frequencies, &corpus, k1, b)))
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        scored.sort_by(|a, b| Self::compare_ranked((a.1, a.0.id), (b.1, b.0.id), false));
+
+        Ok(scored
+            .into_iter()
+            .take(limit)
+            .map(|(item, score)| (item.content.clone(), score))
+            .collect())
     }
 
-    /// Clear all tasks
-    pub fn clear(&self) -> PyResult<()> {
-        let mut tasks = self.tasks.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+    /// Average item length in tokens across the store (O(1))
+    pub fn average_document_length(&self) -> PyResult<f64> {
+        let corpus = self.corpus.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire corpus lock: {}",
+                e
+            ))
         })?;
+        Ok(corpus.average_document_length())
+    }
 
-        tasks.clear();
+    /// Find items similar to the stored item `id`, using its own term frequencies as the
+    /// query. Returns up to `limit` (id, similarity) pairs scoring at least
+    /// `min_similarity`, best first (ties by id), never including the item itself.
+    pub fn related(&self, id: u64, limit: usize, min_similarity: f64) -> PyResult<Vec<(u64, f64)>> {
+        let data = self.data.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
+
+        let source = data.iter().find(|item| item.id == id).ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Memory item {} not found", id))
+        })?;
+
+        let mut related: Vec<(u64, f64)> = data
+            .iter()
+            .zip(self.score_items(&data, &source.word_frequencies)?)
+            .filter(|(item, score)| item.id != id && *score >= min_similarity)
+            .map(|(item, score)| (item.id, score))
+            .collect();
+
+        related.sort_by(|a, b| Self::compare_ranked((a.1, a.0), (b.1, b.0), false));
+        related.truncate(limit);
+        Ok(related)
+    }
+
+    /// Rank items by centrality: each item's average TF-IDF cosine similarity to every
+    /// other item.
+    ///
+    /// Returns up to `limit` (id, average_similarity) pairs, most central first (ties by
+    /// id). Like searches, a store with fewer than `min_docs_for_idf` items (see the
+    /// constructor) compares items by plain TF cosine instead. The most central items tend
+    /// to capture the corpus's core themes. This compares every pair of items (O(n²)), so
+    /// it is meant for moderate corpora of a few thousand items rather than very large
+    /// stores.
+    pub fn central_memories(&self, limit: usize) -> PyResult<Vec<(u64, f64)>> {
+        let data = self.data.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
+
+        let corpus = self.corpus.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire corpus lock: {}",
+                e
+            ))
+        })?;
+
+        // Similarity is symmetric, so score each pair once and credit both items
+        let plain = corpus.document_count < self.min_docs_for_idf.load(std::sync::atomic::Ordering::SeqCst);
+        let mut totals = vec![0.0; data.len()];
+        for i in 0..data.len() {
+            for j in (i + 1)..data.len() {
+                let similarity = if plain {
+                    Self::calculate_cosine_similarity(
+                        &data[i].word_frequencies,
+                        data[i].term_norm(),
+                        &data[j].word_frequencies,
+                        data[j].term_norm(),
+                    )
+                } else {
+                    Self::calculate_tfidf_similarity(
+                        &data[i].word_frequencies,
+                        data[i].tfidf_norm(&corpus),
+                        &data[j].word_frequencies,
+                        data[j].tfidf_norm(&corpus),
+                        &corpus,
+                    )
+                };
+                totals[i] += similarity;
+                totals[j] += similarity;
+            }
+        }
+
+        let others = data.len().saturating_sub(1).max(1) as f64;
+        let mut central: Vec<(u64, f64)> = data
+            .iter()
+            .zip(totals)
+            .map(|(item, total)| (item.id, total / others))
+            .collect();
+
+        central.sort_by(|a, b| Self::compare_ranked((a.1, a.0), (b.1, b.0), false));
+        central.truncate(limit);
+        Ok(central)
+    }
+
+    /// Set the item count below which searches score by plain TF cosine instead of
+    /// TF-IDF (see the constructor's `min_docs_for_idf`). Takes effect on the next search.
+    pub fn set_min_docs_for_idf(&self, min_docs_for_idf: usize) {
+        self.min_docs_for_idf.store(min_docs_for_idf, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// Enable or disable lowercasing during tokenization.
+    ///
+    /// Applies to subsequent saves and queries; call `reindex` so already-stored
+    /// items are tokenized the same way.
+    pub fn set_lowercase(&self, lowercase: bool) -> PyResult<()> {
+        let mut tokenizer = self.tokenizer.write().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire tokenizer lock: {}",
+                e
+            ))
+        })?;
+        tokenizer.lowercase = lowercase;
         Ok(())
     }
-}
 
-/// A high-performance SQLite wrapper with FTS5 support
-#[pyclass]
-pub struct RustSQLiteWrapper {
-    connection_pool: Arc<Mutex<r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>>>,
-}
+    /// Recompute word frequencies for every stored item using the current tokenizer
+    /// settings, keeping ids and content intact
+    pub fn reindex(&self) -> PyResult<()> {
+        let mut data = self.data.write().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
 
-#[pymethods]
-impl RustSQLiteWrapper {
-    #[new]
-    pub fn new(db_path: &str, pool_size: u32) -> PyResult<Self> {
-        let manager = r2d2_sqlite::SqliteConnectionManager::file(db_path);
-        let pool = r2d2::Pool::builder()
-            .max_size(pool_size)
-            .build(manager)
+        let tokenizer = self.tokenizer.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire tokenizer lock: {}",
+                e
+            ))
+        })?;
+
+        let mut corpus = self.corpus.write().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire corpus lock: {}",
+                e
+            ))
+        })?;
+
+        *corpus = CorpusStats::default();
+        for item in data.iter_mut() {
+            item.word_frequencies = tokenizer.compute_word_frequencies(&item.content);
+            item.token_count = token_count(&item.word_frequencies);
+            item.term_norm = std::sync::OnceLock::from(Self::calculate_term_norm(&item.word_frequencies));
+            corpus.add_document(&item.word_frequencies);
+        }
+        self.set_accounted_bytes(data.iter().map(MemoryItem::estimated_bytes).sum());
+        Ok(())
+    }
+
+    /// Estimated bytes held by this store's items: content plus the per-term frequency
+    /// entries. This is the amount registered in the global `total_memory_bytes()`.
+    pub fn memory_usage_bytes(&self) -> usize {
+        self.accounted_bytes.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Independent copy of this store (items, ids, tokenizer settings and corpus stats).
+    ///
+    /// Item ids are preserved, so the copy can later be compared against the live store
+    /// with `diff`. The copy counts toward the global memory budget like any other store.
+    /// It has no journal attached.
+    pub fn snapshot(&self) -> PyResult<RustMemoryStorage> {
+        let data = self.data.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
+
+        let next_id = self.next_id.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire id lock: {}",
+                e
+            ))
+        })?;
+
+        let tokenizer = self.tokenizer.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire tokenizer lock: {}",
+                e
+            ))
+        })?;
+
+        let corpus = self.corpus.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire corpus lock: {}",
+                e
+            ))
+        })?;
+
+        let copy = RustMemoryStorage {
+            data: Arc::new(std::sync::RwLock::new(data.clone())),
+            next_id: Arc::new(Mutex::new(*next_id)),
+            cursors: Arc::new(Mutex::new(HashMap::new())),
+            next_cursor_id: std::sync::atomic::AtomicU64::new(0),
+            tokenizer: Arc::new(std::sync::RwLock::new(tokenizer.clone())),
+            corpus: Arc::new(std::sync::RwLock::new(corpus.clone())),
+            accounted_bytes: std::sync::atomic::AtomicUsize::new(0),
+            journal: Mutex::new(None),
+            normalize_content: self.normalize_content,
+            min_docs_for_idf: std::sync::atomic::AtomicUsize::new(
+                self.min_docs_for_idf.load(std::sync::atomic::Ordering::SeqCst),
+            ),
+            dedup: self.dedup,
+            dedup_threshold: self.dedup_threshold,
+        };
+        copy.reserve_global_bytes(self.memory_usage_bytes())?;
+        Ok(copy)
+    }
+
+    /// Compare this store against `other` (typically an earlier `snapshot`) by item id.
+    ///
+    /// Returns ids only in this store under `"added"`, ids only in `other` under
+    /// `"removed"`, and ids in both whose content differs under `"modified"`, each sorted
+    /// ascending. Items are matched by id and exact content, not by similarity.
+    pub fn diff(&self, other: &RustMemoryStorage) -> PyResult<HashMap<String, Vec<u64>>> {
+        let mut result = HashMap::from([
+            ("added".to_string(), Vec::new()),
+            ("removed".to_string(), Vec::new()),
+            ("modified".to_string(), Vec::new()),
+        ]);
+        if Arc::ptr_eq(&self.data, &other.data) {
+            return Ok(result);
+        }
+
+        // Copy the other side out first so the two stores are never locked together
+        let theirs: HashMap<u64, String> = other
+            .data
+            .read()
             .map_err(|e| {
                 PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Failed to create connection pool: {}",
+                    "Failed to acquire lock: {}",
                     e
                 ))
-            })?;
+            })?
+            .iter()
+            .map(|item| (item.id, item.content.clone()))
+            .collect();
 
-        // Initialize database schema with FTS5 for full-text search
-        {
-            let conn = pool.get().map_err(|e| {
+        let data = self.data.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
+
+        let mut ours = std::collections::HashSet::new();
+        for item in data.iter() {
+            ours.insert(item.id);
+            let bucket = match theirs.get(&item.id) {
+                None => "added",
+                Some(content) if *content != item.content => "modified",
+                Some(_) => continue,
+            };
+            result.entry(bucket.to_string()).or_default().push(item.id);
+        }
+        result
+            .entry("removed".to_string())
+            .or_default()
+            .extend(theirs.keys().filter(|id| !ours.contains(*id)));
+
+        for ids in result.values_mut() {
+            ids.sort_unstable();
+        }
+        Ok(result)
+    }
+
+    /// Remove every stored item and release its share of the global memory budget
+    pub fn clear(&self) -> PyResult<()> {
+        let mut data = self.data.write().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
+
+        let mut corpus = self.corpus.write().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire corpus lock: {}",
+                e
+            ))
+        })?;
+
+        self.journal_append(&JournalRecord::Clear)?;
+        data.clear();
+        *corpus = CorpusStats::default();
+        self.set_accounted_bytes(0);
+        Ok(())
+    }
+
+    /// Rebuild a store from a journal written via `journal_path`, applying records in order.
+    ///
+    /// Item ids and creation times are preserved, and the returned store keeps appending to
+    /// the same journal. A truncated final line (a crash mid-write) is dropped from the
+    /// file; any other malformed record raises `ValueError`.
+    ///
+    /// Store settings are not journaled, so pass the ones the original store used: the
+    /// tokenizer options (`min_token_len`, stopwords and `cjk_mode`) so replayed items are
+    /// indexed the same way, and `min_docs_for_idf`, `dedup` and `dedup_threshold`, which
+    /// take the constructor's defaults otherwise. Journaled content is already normalized
+    /// and deduplicated; `normalize_content` and dedup only apply to saves made after the
+    /// replay.
+    #[staticmethod]
+    #[pyo3(signature = (path, min_token_len=1, journal_fsync=false, normalize_content=false, stopwords=None, english_stopwords=false, cjk_mode=false, min_docs_for_idf=0, dedup=false, dedup_threshold=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn replay_journal(
+        path: &str,
+        min_token_len: usize,
+        journal_fsync: bool,
+        normalize_content: bool,
+        stopwords: Option<Vec<String>>,
+        english_stopwords: bool,
+        cjk_mode: bool,
+        min_docs_for_idf: usize,
+        dedup: bool,
+        dedup_threshold: Option<f64>,
+    ) -> PyResult<RustMemoryStorage> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                "Failed to read journal '{}': {}",
+                path, e
+            ))
+        })?;
+
+        let store = RustMemoryStorage::new(
+            min_token_len,
+            None,
+            false,
+            normalize_content,
+            stopwords,
+            english_stopwords,
+            min_docs_for_idf,
+            dedup,
+            dedup_threshold,
+            cjk_mode,
+        )?;
+        {
+            let mut data = store.data.write().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire lock: {}",
+                    e
+                ))
+            })?;
+            let mut next_id = store.next_id.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire id lock: {}",
+                    e
+                ))
+            })?;
+            let tokenizer = store.tokenizer.read().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire tokenizer lock: {}",
+                    e
+                ))
+            })?;
+            let mut corpus = store.corpus.write().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire corpus lock: {}",
+                    e
+                ))
+            })?;
+
+            let lines: Vec<&str> = contents.lines().collect();
+            let mut truncated_tail = false;
+            for (index, line) in lines.iter().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let record: JournalRecord = match serde_json::from_str(line) {
+                    Ok(record) => record,
+                    Err(_) if index + 1 == lines.len() && !contents.ends_with('\n') => {
+                        log::warn!("Dropping truncated final record in journal '{}'", path);
+                        truncated_tail = true;
+                        break;
+                    }
+                    Err(e) => {
+                        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                            "Invalid journal record on line {}: {}",
+                            index + 1,
+                            e
+                        )))
+                    }
+                };
+
+                match record {
+                    JournalRecord::Header { format_version } => check_format_version(format_version)?,
+                    JournalRecord::Save { id, content, created_at } => {
+                        let mut item = Self::build_item(&tokenizer, id, &content);
+                        if let Some(created_at) = created_at {
+                            item.created_at = created_at;
+                        }
+                        corpus.add_document(&item.word_frequencies);
+                        data.push(item);
+                        *next_id = (*next_id).max(id + 1);
+                    }
+                    JournalRecord::Delete { id } => {
+                        if let Ok(index) = data.binary_search_by_key(&id, |item| item.id) {
+                            let item = data.remove(index);
+                            corpus.remove_document(&item.word_frequencies);
+                        }
+                    }
+                    JournalRecord::Clear => {
+                        data.clear();
+                        *corpus = CorpusStats::default();
+                    }
+                }
+            }
+
+            store.reserve_global_bytes(data.iter().map(MemoryItem::estimated_bytes).sum())?;
+
+            // Cut the partial record off so new appends start on a fresh line
+            if truncated_tail {
+                let valid_len = contents.rfind('\n').map(|i| i + 1).unwrap_or(0);
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .open(path)
+                    .and_then(|file| file.set_len(valid_len as u64))
+                    .map_err(|e| {
+                        PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                            "Failed to truncate journal '{}': {}",
+                            path, e
+                        ))
+                    })?;
+            }
+        }
+
+        *store.journal.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire journal lock: {}",
+                e
+            ))
+        })? = Some(JournalWriter::open(path, journal_fsync)?);
+        Ok(store)
+    }
+
+    /// Rewrite the journal as one `save` record per stored item, discarding the history
+    /// of cleared items. The new journal is written to a temporary file, fsynced and
+    /// renamed over the old one, so a crash during compaction leaves either version intact.
+    pub fn compact_journal(&self) -> PyResult<()> {
+        use std::io::Write;
+
+        let data = self.data.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
+
+        let mut journal = self.journal.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire journal lock: {}",
+                e
+            ))
+        })?;
+
+        let writer = journal.as_ref().ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>("Store was not opened with a journal_path")
+        })?;
+        let path = writer.path.clone();
+        let fsync = writer.fsync;
+
+        let header = JournalRecord::Header {
+            format_version: FORMAT_VERSION,
+        };
+        let saves = data.iter().map(|item| JournalRecord::Save {
+            id: item.id,
+            content: item.content.clone(),
+            created_at: Some(item.created_at),
+        });
+
+        let mut contents = String::new();
+        for record in std::iter::once(header).chain(saves) {
+            contents.push_str(&serde_json::to_string(&record).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to serialize journal record: {}",
+                    e
+                ))
+            })?);
+            contents.push('\n');
+        }
+
+        let tmp_path = format!("{}.tmp", path);
+        std::fs::File::create(&tmp_path)
+            .and_then(|mut file| {
+                file.write_all(contents.as_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|_| std::fs::rename(&tmp_path, &path))
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                    "Failed to compact journal '{}': {}",
+                    path, e
+                ))
+            })?;
+
+        *journal = Some(JournalWriter::open(&path, fsync)?);
+        Ok(())
+    }
+
+    /// Write every stored item, including its id and precomputed word frequencies, to
+    /// `path` as JSON. The file is written to a temporary path and renamed into place, so
+    /// an interrupted save never leaves a half-written file behind.
+    pub fn save_to_file(&self, path: &str) -> PyResult<()> {
+        let json = {
+            let data = self.data.read().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire lock: {}",
+                    e
+                ))
+            })?;
+
+            let next_id = self.next_id.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire id lock: {}",
+                    e
+                ))
+            })?;
+
+            serde_json::to_string(&MemoryStoreFile {
+                format_version: FORMAT_VERSION,
+                next_id: *next_id,
+                items: data.clone(),
+            })
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to serialize to JSON: {}",
+                    e
+                ))
+            })?
+        };
+
+        let tmp_path = format!("{}.tmp", path);
+        std::fs::write(&tmp_path, json)
+            .and_then(|_| std::fs::rename(&tmp_path, path))
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                    "Failed to write memory file '{}': {}",
+                    path, e
+                ))
+            })
+    }
+
+    /// Replace this store's contents with the items in a file written by `save_to_file`.
+    ///
+    /// Ids and word frequencies are restored as saved, so a store with different tokenizer
+    /// settings should call `reindex` afterwards. New saves continue past the highest id
+    /// ever handed out by the saving store. If this store has a journal, the load is
+    /// journaled as a `clear` followed by one `save` per item. Open cursors are closed,
+    /// since their positions refer to the replaced items: `fetch` on one raises `ValueError`.
+    pub fn load_from_file(&self, path: &str) -> PyResult<()> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                "Failed to read memory file '{}': {}",
+                path, e
+            ))
+        })?;
+        let mut file: MemoryStoreFile = parse_versioned(&contents)?;
+
+        // Searches and cursors rely on items being in id order
+        file.items.sort_by_key(|item| item.id);
+        if let Some(pair) = file.items.windows(2).find(|pair| pair[0].id == pair[1].id) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Duplicate memory item id {} in '{}'",
+                pair[0].id, path
+            )));
+        }
+
+        let mut data = self.data.write().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
+
+        let mut next_id = self.next_id.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire id lock: {}",
+                e
+            ))
+        })?;
+
+        let mut corpus = self.corpus.write().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire corpus lock: {}",
+                e
+            ))
+        })?;
+
+        let mut cursors = self.cursors.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cursor lock: {}",
+                e
+            ))
+        })?;
+
+        let loaded_bytes: usize = file.items.iter().map(MemoryItem::estimated_bytes).sum();
+        self.reserve_global_bytes(loaded_bytes)?;
+
+        let journaled = self.journal_append(&JournalRecord::Clear).and_then(|_| {
+            file.items.iter().try_for_each(|item| {
+                self.journal_append(&JournalRecord::Save {
+                    id: item.id,
+                    content: item.content.clone(),
+                    created_at: Some(item.created_at),
+                })
+            })
+        });
+        if let Err(e) = journaled {
+            self.set_accounted_bytes(self.memory_usage_bytes() - loaded_bytes);
+            return Err(e);
+        }
+
+        *corpus = CorpusStats::default();
+        for item in &file.items {
+            corpus.add_document(&item.word_frequencies);
+        }
+        let max_id = file.items.last().map(|item| item.id + 1).unwrap_or(0);
+        *next_id = file.next_id.max(max_id);
+        *data = file.items;
+        cursors.clear();
+        self.set_accounted_bytes(loaded_bytes);
+        Ok(())
+    }
+
+    /// Number of distinct terms across all stored items (O(1))
+    pub fn vocabulary_size(&self) -> PyResult<usize> {
+        let corpus = self.corpus.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire corpus lock: {}",
+                e
+            ))
+        })?;
+        Ok(corpus.document_frequencies.len())
+    }
+
+    /// Most frequent terms across all stored items with their total corpus frequency,
+    /// sorted descending (ties by term). `top_n = 0` returns every term.
+    pub fn term_frequencies(&self, top_n: usize) -> PyResult<Vec<(String, f64)>> {
+        let corpus = self.corpus.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire corpus lock: {}",
+                e
+            ))
+        })?;
+
+        let mut terms: Vec<(String, f64)> = corpus
+            .term_totals
+            .iter()
+            .map(|(term, total)| (term.clone(), *total))
+            .collect();
+        terms.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+
+        if top_n > 0 {
+            terms.truncate(top_n);
+        }
+        Ok(terms)
+    }
+
+    /// Total number of tokens across all stored items (O(1))
+    pub fn total_tokens(&self) -> PyResult<usize> {
+        let corpus = self.corpus.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire corpus lock: {}",
+                e
+            ))
+        })?;
+        Ok(corpus.total_tokens)
+    }
+
+    /// Open a cursor over the stored items - returns a cursor ID for use with fetch
+    pub fn open_cursor(&self) -> PyResult<u64> {
+        let mut cursors = self.cursors.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cursor lock: {}",
+                e
+            ))
+        })?;
+
+        let cursor_id = self
+            .next_cursor_id
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        cursors.insert(cursor_id, 0);
+        Ok(cursor_id)
+    }
+
+    /// Fetch the next n items from a cursor (empty once the cursor is exhausted)
+    pub fn fetch(&self, cursor_id: u64, n: usize) -> PyResult<Vec<String>> {
+        let data = self.data.read().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
+
+        let mut cursors = self.cursors.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cursor lock: {}",
+                e
+            ))
+        })?;
+
+        let position = cursors.get_mut(&cursor_id).ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Cursor {} not found or already closed",
+                cursor_id
+            ))
+        })?;
+
+        let start = (*position).min(data.len());
+        let end = start.saturating_add(n).min(data.len());
+        *position = end;
+
+        Ok(data[start..end].iter().map(|item| item.content.clone()).collect())
+    }
+
+    /// Close a cursor and release its position
+    pub fn close_cursor(&self, cursor_id: u64) -> PyResult<()> {
+        let mut cursors = self.cursors.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cursor lock: {}",
+                e
+            ))
+        })?;
+
+        if cursors.remove(&cursor_id).is_none() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Cursor {} not found or already closed",
+                cursor_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Search stored items by TF-IDF cosine similarity to the query.
+    ///
+    /// Each term's frequency is weighted by the smoothed IDF `ln(1 + N / df)`, where `N` is
+    /// the number of stored items and `df` the number containing the term, so terms that
+    /// appear everywhere count for less than rare ones but never for nothing: an exact
+    /// match scores above 0 even in a one-item store. Until the store holds
+    /// `min_docs_for_idf` items (see the constructor), plain TF cosine is used instead.
+    ///
+    /// Equal scores are ordered by item id: `tie_break="oldest"` (default) puts earlier
+    /// insertions first, `tie_break="newest"` prefers the most recent items.
+    ///
+    /// `length_penalty` keeps very short items from topping the ranking on a single
+    /// shared term: an item with fewer than `min_tokens` stored tokens has its score
+    /// multiplied by `(token_count / min_tokens) ^ length_penalty`. The default of 0.0
+    /// leaves scores unchanged; larger values penalize short items more steeply.
+    ///
+    /// `min_score` drops items scoring below it (after any length penalty) before the
+    /// results are cut to `limit`, so any threshold above 0.0 excludes items that share no
+    /// weighted terms with the query. The default of 0.0 returns up to `limit` items; a NaN
+    /// or infinite threshold raises `ValueError`.
+    ///
+    /// Every returned item has its access count incremented (see `search_with_activation`),
+    /// so searching mutates the store; pass `record_access=False` for a read-only query that
+    /// leaves the counters untouched.
+    ///
+    /// The GIL is released while ranking, and searches share a read lock on the store,
+    /// so concurrent searches from several Python threads run in parallel; only writes
+    /// such as `save` and `delete` take exclusive locks.
+    #[pyo3(signature = (query, limit, tie_break="oldest", length_penalty=0.0, min_tokens=5, min_score=0.0, record_access=true))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn search(
+        &self,
+        py: Python<'_>,
+        query: &str,
+        limit: usize,
+        tie_break: &str,
+        length_penalty: f64,
+        min_tokens: usize,
+        min_score: f64,
+        record_access: bool,
+    ) -> PyResult<Vec<String>> {
+        if length_penalty < 0.0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "length_penalty must be non-negative, got {}",
+                length_penalty
+            )));
+        }
+        if !min_score.is_finite() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "min_score must be finite, got {}",
+                min_score
+            )));
+        }
+
+        let newest_first = match tie_break {
+            "oldest" => false,
+            "newest" => true,
+            other => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Invalid tie_break '{}': expected 'oldest' or 'newest'",
+                    other
+                )))
+            }
+        };
+
+        // Ranking only needs shared locks, so other Python threads can search meanwhile
+        let results = py.allow_threads(|| {
+            self.ranked_search(query, limit, newest_first, length_penalty, min_tokens, min_score, record_access)
+        })?;
+        Ok(results.into_iter().map(|(_, content, _)| content).collect())
+    }
+
+    /// Search like `search` with its default options, returning `(id, content, score)`
+    /// in ranked order so results can be followed up with `delete` and other id-based
+    /// calls. Like `search`, this counts as an access of every returned item unless
+    /// `record_access` is false.
+    #[pyo3(signature = (query, limit, record_access=true))]
+    pub fn search_full(
+        &self,
+        py: Python<'_>,
+        query: &str,
+        limit: usize,
+        record_access: bool,
+    ) -> PyResult<Vec<(u64, String, f64)>> {
+        py.allow_threads(|| self.ranked_search(query, limit, false, 0.0, 0, 0.0, record_access))
+    }
+
+    /// Export every item's TF-IDF vector for use outside the store (e.g. scikit-learn).
+    ///
+    /// Returns the vocabulary, the sorted list of every stored term defining the vector
+    /// dimensions, and `(id, vector)` per item in id order. Each component is the term's
+    /// count in the item times `ln(1 + N / df)`, the weighting `search` uses, so vectors
+    /// reflect the IDF of the corpus at the time of the call and change as items are saved
+    /// or deleted. While the store holds fewer than `min_docs_for_idf` items, components
+    /// are the plain term counts instead, matching search's TF fallback. They are not
+    /// normalized. Dense vectors grow with the vocabulary; see `export_vectors_sparse` for
+    /// large corpora.
+    pub fn export_vectors(&self, py: Python<'_>) -> PyResult<ExportedVectors<Vec<f64>>> {
+        py.allow_threads(|| {
+            let (vocabulary, sparse) = self.tfidf_vectors()?;
+            let dense = sparse
+                .into_iter()
+                .map(|(id, weights)| {
+                    let mut vector = vec![0.0; vocabulary.len()];
+                    for (i, weight) in weights {
+                        vector[i] = weight;
+                    }
+                    (id, vector)
+                })
+                .collect();
+            Ok((vocabulary, dense))
+        })
+    }
+
+    /// Like `export_vectors`, but each item's vector is a list of `(dimension, weight)`
+    /// pairs for its nonzero components only, sorted by dimension - ready for building a
+    /// `scipy.sparse` matrix.
+    pub fn export_vectors_sparse(&self, py: Python<'_>) -> PyResult<ExportedVectors<Vec<(usize, f64)>>> {
+        py.allow_threads(|| self.tfidf_vectors())
+    }
+}
+
+/// Search several memory stores (shards) and merge their results into one ranking.
+///
+/// Each store is searched like `RustMemoryStorage.search_full` (counting as an access of
+/// its returned items), then all candidates are re-sorted by score and the global top
+/// `limit` returned as `(content, score)`; equal scores keep store order. Scores are only
+/// comparable across stores that share tokenizer settings and `min_docs_for_idf`, and
+/// since IDF is per-store, shards should hold similarly distributed content.
+/// Stores are searched one after another with the GIL released; searching them in
+/// parallel is left as a follow-up.
+#[pyfunction]
+fn federated_search(
+    py: Python<'_>,
+    stores: Vec<PyRef<'_, RustMemoryStorage>>,
+    query: &str,
+    limit: usize,
+) -> PyResult<Vec<(String, f64)>> {
+    let shards: Vec<&RustMemoryStorage> = stores.iter().map(|store| &**store).collect();
+    py.allow_threads(|| {
+        let mut merged = Vec::new();
+        for shard in shards {
+            let results = shard.ranked_search(query, limit, false, 0.0, 0, 0.0, true)?;
+            merged.extend(results.into_iter().map(|(_, content, score)| (content, score)));
+        }
+
+        merged.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        merged.truncate(limit);
+        Ok(merged)
+    })
+}
+
+// Helper function to check a JSON value against the JSON Schema subset RustToolExecutor
+// supports, returning a description of the first violation (naming its path, "$" being
+// the root) or None if the value conforms
+fn schema_violation(schema: &serde_json::Value, value: &serde_json::Value, path: &str) -> Option<String> {
+    use serde_json::Value;
+
+    let schema = schema.as_object()?;
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let matches = |name: &str| match name {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "boolean" => value.is_boolean(),
+            "null" => value.is_null(),
+            "number" => value.is_number(),
+            "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+            _ => false,
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|name| matches(name)) {
+            let actual = match value {
+                Value::Null => "null",
+                Value::Bool(_) => "boolean",
+                Value::Number(_) => "number",
+                Value::String(_) => "string",
+                Value::Array(_) => "array",
+                Value::Object(_) => "object",
+            };
+            return Some(format!("'{}': expected {}, got {}", path, allowed.join(" or "), actual));
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            return Some(format!("'{}': {} is not one of the allowed values", path, value));
+        }
+    }
+
+    match value {
+        Value::Object(fields) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !fields.contains_key(name) {
+                        return Some(format!("'{}.{}': required field is missing", path, name));
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, field) in fields {
+                let field_path = format!("{}.{}", path, name);
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(field_schema) => {
+                        if let Some(violation) = schema_violation(field_schema, field, &field_path) {
+                            return Some(violation);
+                        }
+                    }
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            return Some(format!("'{}': unexpected field", field_path));
+                        }
+                        Some(extra @ Value::Object(_)) => {
+                            if let Some(violation) = schema_violation(extra, field, &field_path) {
+                                return Some(violation);
+                            }
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            let len = items.len() as u64;
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64).filter(|min| len < *min) {
+                return Some(format!("'{}': expected at least {} items, got {}", path, min, len));
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64).filter(|max| len > *max) {
+                return Some(format!("'{}': expected at most {} items, got {}", path, max, len));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    if let Some(violation) = schema_violation(item_schema, item, &format!("{}[{}]", path, i)) {
+                        return Some(violation);
+                    }
+                }
+            }
+        }
+        Value::String(text) => {
+            let len = text.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64).filter(|min| len < *min) {
+                return Some(format!("'{}': expected at least {} characters, got {}", path, min, len));
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64).filter(|max| len > *max) {
+                return Some(format!("'{}': expected at most {} characters, got {}", path, max, len));
+            }
+        }
+        Value::Number(number) => {
+            let n = number.as_f64().unwrap_or(0.0);
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64).filter(|min| n < *min) {
+                return Some(format!("'{}': {} is less than the minimum {}", path, number, min));
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64).filter(|max| n > *max) {
+                return Some(format!("'{}': {} is greater than the maximum {}", path, number, max));
+            }
+        }
+        _ => {}
+    }
+
+    None
+}
+
+// Helper function to fill keys missing from `target` with registered defaults. With `deep`,
+// a key present on both sides as objects is merged recursively instead of kept as is.
+fn merge_defaults(
+    target: &mut serde_json::Map<String, serde_json::Value>,
+    defaults: &serde_json::Map<String, serde_json::Value>,
+    deep: bool,
+) {
+    for (key, default) in defaults {
+        match (target.get_mut(key), default) {
+            (None, _) => {
+                target.insert(key.clone(), default.clone());
+            }
+            (Some(serde_json::Value::Object(nested)), serde_json::Value::Object(nested_defaults)) if deep => {
+                merge_defaults(nested, nested_defaults, deep);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Tool execution result for caching
+#[derive(Debug, Clone)]
+struct CachedResult {
+    tool_name: String,
+    /// Arguments the result was produced from, so the entry can be refetched
+    args: String,
+    result: CachedValue,
+    timestamp: std::time::Instant,
+    /// Pinned entries are never evicted or expired until unpinned
+    pinned: bool,
+    /// Number of cache hits served by this entry
+    hits: usize,
+    /// When the entry was last stored or served, for least-recently-used eviction
+    last_used: std::time::Instant,
+}
+
+/// A cached result as stored: plain, or gzip-compressed when `compress_cache` is on
+#[derive(Debug, Clone)]
+enum CachedValue {
+    Plain(String),
+    Compressed(Vec<u8>),
+}
+
+/// Results shorter than this many UTF-8 bytes are cached uncompressed even with
+/// `compress_cache`, since gzip's header and setup cost outweigh the savings
+const COMPRESSION_MIN_BYTES: usize = 512;
+
+impl CachedValue {
+    // Helper function to gzip a result, returning None when it doesn't shrink
+    fn compress(result: &str) -> Option<Vec<u8>> {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(result.as_bytes()).ok()?;
+        let compressed = encoder.finish().ok()?;
+        (compressed.len() < result.len()).then_some(compressed)
+    }
+
+    // Helper function to get the stored size of a compressed value (None when plain)
+    fn compressed_len(&self) -> Option<usize> {
+        match self {
+            CachedValue::Plain(_) => None,
+            CachedValue::Compressed(bytes) => Some(bytes.len()),
+        }
+    }
+
+    // Helper function to recover the result text, decompressing if needed
+    fn text(&self) -> PyResult<String> {
+        use std::io::Read;
+
+        match self {
+            CachedValue::Plain(result) => Ok(result.clone()),
+            CachedValue::Compressed(bytes) => {
+                let mut result = String::new();
+                flate2::read::GzDecoder::new(bytes.as_slice())
+                    .read_to_string(&mut result)
+                    .map_err(|e| {
+                        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                            "Failed to decompress cached result: {}",
+                            e
+                        ))
+                    })?;
+                Ok(result)
+            }
+        }
+    }
+}
+
+impl CachedResult {
+    fn is_expired(&self, ttl_secs: u64) -> bool {
+        !self.pinned && self.timestamp.elapsed().as_secs() >= ttl_secs
+    }
+}
+
+/// One cached result in a portable cache manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ManifestEntry {
+    tool: String,
+    args: String,
+    result: String,
+    /// Seconds since the result was cached, at export time
+    age_secs: u64,
+    #[serde(default)]
+    pinned: bool,
+}
+
+/// JSON document written by `export_manifest` and read by `import_manifest`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheManifest {
+    #[serde(default = "default_format_version")]
+    format_version: u64,
+    entries: Vec<ManifestEntry>,
+}
+
+/// One cached result in a cache file written by `export_cache`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheFileEntry {
+    tool: String,
+    args: String,
+    result: String,
+    /// Unix time (seconds) at which the entry expires under the exporting executor's TTL
+    expires_at: u64,
+    #[serde(default)]
+    pinned: bool,
+}
+
+/// File written by `export_cache` and read by `import_cache`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheFile {
+    #[serde(default = "default_format_version")]
+    format_version: u64,
+    entries: Vec<CacheFileEntry>,
+}
+
+/// Recurring per-tool cache purges, shared between an executor and its purge thread
+#[derive(Debug, Default)]
+struct PurgeSchedule {
+    /// tool_name -> (interval, next purge deadline)
+    purges: HashMap<String, (std::time::Duration, std::time::Instant)>,
+    /// Set when the executor is dropped, telling the purge thread to exit
+    shutdown: bool,
+}
+
+/// Process-wide count of in-flight tool executions across all RustToolExecutor instances
+#[derive(Debug)]
+struct ExecutionGate {
+    in_flight: usize,
+    /// Most executions allowed in flight at once (None = no limit)
+    limit: Option<usize>,
+    /// Wait for a free slot instead of raising when the limit is reached
+    blocking: bool,
+}
+
+static GLOBAL_EXECUTIONS: Mutex<ExecutionGate> = Mutex::new(ExecutionGate {
+    in_flight: 0,
+    limit: None,
+    blocking: false,
+});
+
+/// Signalled whenever a slot in `GLOBAL_EXECUTIONS` is released or the limit changes
+static GLOBAL_EXECUTION_RELEASED: std::sync::Condvar = std::sync::Condvar::new();
+
+/// Set (or with `None`, remove) a process-wide cap on tool executions in flight at once,
+/// shared by every `RustToolExecutor`.
+///
+/// `begin_execution` takes a slot and `end_execution` returns it. When every slot is taken,
+/// `begin_execution` raises `RuntimeError`, or with `blocking=True` waits (with the GIL
+/// released) until another execution ends. The cap applies on top of each executor's own
+/// `max_recursion_depth`, which is checked first: an execution must pass both. Lowering
+/// the cap below the current count doesn't interrupt anything; new executions just wait
+/// or fail until enough have ended.
+#[pyfunction]
+#[pyo3(signature = (limit=None, blocking=false))]
+fn set_global_execution_limit(limit: Option<usize>, blocking: bool) -> PyResult<()> {
+    let mut gate = GLOBAL_EXECUTIONS.lock().map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+    })?;
+    gate.limit = limit;
+    gate.blocking = blocking;
+    GLOBAL_EXECUTION_RELEASED.notify_all();
+    Ok(())
+}
+
+/// Number of tool executions currently in flight across all executors in this process
+#[pyfunction]
+fn global_executions_in_flight() -> PyResult<usize> {
+    let gate = GLOBAL_EXECUTIONS.lock().map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+    })?;
+    Ok(gate.in_flight)
+}
+
+// Helper function to take a global execution slot, waiting for one if the gate is blocking
+fn acquire_global_execution(py: Python<'_>) -> PyResult<()> {
+    py.allow_threads(|| {
+        let mut gate = GLOBAL_EXECUTIONS.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        loop {
+            match gate.limit {
+                Some(limit) if gate.in_flight >= limit => {
+                    if !gate.blocking {
+                        return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                            "Global execution limit of {} reached",
+                            limit
+                        )));
+                    }
+                    gate = GLOBAL_EXECUTION_RELEASED.wait(gate).map_err(|e| {
+                        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+                    })?;
+                }
+                _ => {
+                    gate.in_flight += 1;
+                    return Ok(());
+                }
+            }
+        }
+    })
+}
+
+// Helper function to return global execution slots taken by `acquire_global_execution`
+fn release_global_executions(count: usize) {
+    if count == 0 {
+        return;
+    }
+    if let Ok(mut gate) = GLOBAL_EXECUTIONS.lock() {
+        gate.in_flight = gate.in_flight.saturating_sub(count);
+    }
+    GLOBAL_EXECUTION_RELEASED.notify_all();
+}
+
+/// A high-performance tool execution engine with caching and validation
+#[pyclass]
+pub struct RustToolExecutor {
+    max_recursion_depth: usize,
+    execution_count: Arc<Mutex<usize>>,
+    /// Ids and start times of executions begun but not yet ended, oldest first
+    in_flight: Mutex<Vec<(String, std::time::Instant)>>,
+    /// Sequence number making each execution id unique
+    next_execution_id: std::sync::atomic::AtomicU64,
+    /// Attempts recorded per execution id by `record_attempt`
+    attempts: Mutex<HashMap<String, usize>>,
+    /// Cache for tool results (tool_name + args_hash -> result)
+    result_cache: Arc<Mutex<HashMap<String, CachedResult>>>,
+    /// Cache TTL in seconds
+    cache_ttl_secs: u64,
+    /// Per-tool TTL overrides in seconds (tool_name -> ttl), falling back to `cache_ttl_secs`
+    tool_ttls: Arc<Mutex<HashMap<String, u64>>>,
+    /// Maximum cache size (0 = unlimited) - using AtomicUsize for thread-safe interior mutability
+    max_cache_size: std::sync::atomic::AtomicUsize,
+    /// Execution statistics
+    stats: Arc<Mutex<ExecutionStats>>,
+    /// When the stats were last reset (or the executor created)
+    stats_reset_at: Mutex<std::time::Instant>,
+    /// Registered tool versions (tool_name -> version) folded into cache keys
+    tool_versions: Arc<Mutex<HashMap<String, String>>>,
+    /// Registered JSON Schemas for tool arguments (tool_name -> schema)
+    tool_schemas: Arc<Mutex<HashMap<String, serde_json::Value>>>,
+    /// Registered default arguments (tool_name -> defaults object)
+    tool_defaults: Arc<Mutex<HashMap<String, serde_json::Map<String, serde_json::Value>>>>,
+    /// Results larger than this many UTF-8 bytes are not cached (None = no limit)
+    max_cache_value_bytes: Option<usize>,
+    /// Whether large results are gzip-compressed in the cache
+    compress_cache: bool,
+    /// Called as `callback(tool_name, args, result)` for each evicted entry
+    on_evict: Arc<Mutex<Option<PyObject>>>,
+    /// Scheduled purges plus the condvar that wakes the purge thread when they change
+    purge_schedule: Arc<(Mutex<PurgeSchedule>, std::sync::Condvar)>,
+    /// Background thread running scheduled purges (started by the first `schedule_purge`)
+    purge_thread: Mutex<Option<std::thread::JoinHandle<()>>>,
+}
+
+impl Drop for RustToolExecutor {
+    fn drop(&mut self) {
+        // Stop the purge thread before the cache it sweeps goes away
+        let (schedule, wakeup) = &*self.purge_schedule;
+        if let Ok(mut schedule) = schedule.lock() {
+            schedule.shutdown = true;
+        }
+        wakeup.notify_all();
+        if let Some(handle) = self.purge_thread.get_mut().ok().and_then(|handle| handle.take()) {
+            if handle.join().is_err() {
+                log::warn!("Cache purge thread panicked");
+            }
+        }
+
+        // Executions that never called end_execution would otherwise hold their global slots
+        if let Ok(count) = self.execution_count.lock() {
+            release_global_executions(*count);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ExecutionStats {
+    total_executions: usize,
+    cache_hits: usize,
+    cache_misses: usize,
+    validation_failures: usize,
+    skipped_oversized: usize,
+    /// refresh_expiring callbacks that raised or returned an uncacheable value
+    refresh_failures: usize,
+    /// Calls to `compute` made by get_or_compute / get_or_compute_retry
+    compute_attempts: usize,
+    /// Of those, calls that raised (each one either retried or propagated)
+    compute_failures: usize,
+    /// UTF-8 bytes of results stored compressed, before compression
+    compression_input_bytes: usize,
+    /// Bytes those results occupy compressed
+    compression_output_bytes: usize,
+    /// Per-tool breakdown (tool_name -> counters)
+    per_tool: HashMap<String, ToolStats>,
+}
+
+/// Per-tool execution and cache counters
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ToolStats {
+    /// Calls to `begin_execution` that were allowed to run
+    executions: usize,
+    cache_hits: usize,
+    cache_misses: usize,
+    cache_stores: usize,
+    /// Entries dropped to make room under the cache size limit
+    cache_evictions: usize,
+}
+
+impl ToolStats {
+    // Helper function to name every counter, as reported by `get_tool_stats`
+    fn counters(&self) -> HashMap<String, usize> {
+        let mut counters = HashMap::new();
+        counters.insert("executions".to_string(), self.executions);
+        counters.insert("hits".to_string(), self.cache_hits);
+        counters.insert("misses".to_string(), self.cache_misses);
+        counters.insert("stores".to_string(), self.cache_stores);
+        counters.insert("evictions".to_string(), self.cache_evictions);
+        counters
+    }
+}
+
+impl ExecutionStats {
+    fn tool(&mut self, tool_name: &str) -> &mut ToolStats {
+        self.per_tool.entry(tool_name.to_string()).or_default()
+    }
+
+    // Helper function to flatten every counter into named values, using the `get_stats`
+    // names for global counters and `per_tool.<tool>.<counter>` (with the `get_tool_stats`
+    // counter names) for per-tool ones
+    fn counters(&self) -> HashMap<String, usize> {
+        let mut counters = HashMap::new();
+        counters.insert("total_executions".to_string(), self.total_executions);
+        counters.insert("cache_hits".to_string(), self.cache_hits);
+        counters.insert("cache_misses".to_string(), self.cache_misses);
+        counters.insert("validation_failures".to_string(), self.validation_failures);
+        counters.insert("skipped_oversized".to_string(), self.skipped_oversized);
+        counters.insert("refresh_failures".to_string(), self.refresh_failures);
+        counters.insert("compute_attempts".to_string(), self.compute_attempts);
+        counters.insert("compute_failures".to_string(), self.compute_failures);
+        counters.insert("compression_input_bytes".to_string(), self.compression_input_bytes);
+        counters.insert("compression_output_bytes".to_string(), self.compression_output_bytes);
+        for (tool_name, tool_stats) in &self.per_tool {
+            for (name, value) in tool_stats.counters() {
+                counters.insert(format!("per_tool.{}.{}", tool_name, name), value);
+            }
+        }
+        counters
+    }
+}
+
+/// JSON document written by `stats_snapshot` and read by `diff_stats`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StatsSnapshot {
+    #[serde(default = "default_format_version")]
+    format_version: u64,
+    stats: ExecutionStats,
+}
+
+impl RustToolExecutor {
+    // Helper function run on the purge thread: sleep until the nearest purge deadline (or
+    // until the schedule changes), drop the due tools' unpinned entries, and repeat until
+    // the executor shuts the schedule down
+    fn run_purges(
+        schedule: Arc<(Mutex<PurgeSchedule>, std::sync::Condvar)>,
+        cache: Arc<Mutex<HashMap<String, CachedResult>>>,
+    ) {
+        let (lock, wakeup) = &*schedule;
+        let mut state = match lock.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+
+        while !state.shutdown {
+            let now = std::time::Instant::now();
+            let mut due = std::collections::HashSet::new();
+            for (tool_name, (interval, deadline)) in state.purges.iter_mut() {
+                if *deadline <= now {
+                    due.insert(tool_name.clone());
+                    *deadline = now + *interval;
+                }
+            }
+
+            if !due.is_empty() {
+                if let Ok(mut cache) = cache.lock() {
+                    let before = cache.len();
+                    cache.retain(|_, entry| entry.pinned || !due.contains(&entry.tool_name));
+                    log::debug!("Scheduled purge removed {} cache entries", before - cache.len());
+                }
+            }
+
+            let nearest = state.purges.values().map(|(_, deadline)| *deadline).min();
+            state = match nearest {
+                Some(deadline) => match wakeup.wait_timeout(state, deadline.saturating_duration_since(now)) {
+                    Ok((state, _)) => state,
+                    Err(_) => return,
+                },
+                None => match wakeup.wait(state) {
+                    Ok(state) => state,
+                    Err(_) => return,
+                },
+            };
+        }
+    }
+
+    // Helper function to prepare a result for the cache, compressing it when
+    // `compress_cache` is on, it's large enough, and compression actually shrinks it.
+    // Callers record the compression stats once the value is actually stored.
+    fn pack_result(&self, result: &str) -> CachedValue {
+        if !self.compress_cache || result.len() < COMPRESSION_MIN_BYTES {
+            return CachedValue::Plain(result.to_string());
+        }
+
+        match CachedValue::compress(result) {
+            Some(compressed) => CachedValue::Compressed(compressed),
+            None => CachedValue::Plain(result.to_string()),
+        }
+    }
+
+    // Helper function behind `cache_result` and `import_manifest`: store a result as if it
+    // had been cached `age` ago, optionally pinning it
+    fn store_result(
+        &self,
+        tool_name: &str,
+        args: &str,
+        result: &str,
+        age: std::time::Duration,
+        pin: bool,
+    ) -> PyResult<bool> {
+        if let Some(max_bytes) = self.max_cache_value_bytes {
+            if result.len() > max_bytes {
+                let mut stats = self.stats.lock().map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+                })?;
+                stats.skipped_oversized += 1;
+                return Ok(false);
+            }
+        }
+
+        let cache_key = self.cache_key(tool_name, args)?;
+        // Compress before taking the cache lock so other lookups aren't held up
+        let value = self.pack_result(result);
+        let compressed_len = value.compressed_len();
+
+        let mut cache = self.result_cache.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cache lock: {}",
+                e
+            ))
+        })?;
+
+        // Enforce cache size limit if set (replacing an existing entry doesn't grow the cache)
+        let mut evicted = Vec::new();
+        let max_size = self.max_cache_size.load(std::sync::atomic::Ordering::SeqCst);
+        if max_size > 0 && cache.len() >= max_size && !cache.contains_key(&cache_key) {
+            // First, remove all expired entries
+            let ttls = self.tool_ttls.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?;
+            let expired_keys: Vec<_> = cache
+                .iter()
+                .filter(|(_, v)| v.is_expired(self.effective_ttl(&ttls, &v.tool_name)))
+                .map(|(k, _)| k.clone())
+                .collect();
+            drop(ttls);
+            for key in expired_keys {
+                evicted.extend(cache.remove(&key));
+            }
+
+            // If still over limit, remove the least recently used unpinned entries. Finding
+            // them scans the cache, which is cheap next to the tool call being cached.
+            if cache.len() >= max_size {
+                let to_remove = cache.len() - max_size + 1;
+                let mut candidates: Vec<_> = cache
+                    .iter()
+                    .filter(|(_, v)| !v.pinned)
+                    .map(|(k, v)| (v.last_used, k.clone()))
+                    .collect();
+                if candidates.len() < to_remove {
+                    // Too many pinned entries to make room: keep the cache as is
+                    log::warn!(
+                        "Not caching result for tool '{}': cache is full of pinned entries",
+                        tool_name
+                    );
+                    drop(cache);
+                    self.notify_evicted(evicted)?;
+                    return Ok(false);
+                }
+                // Only the `to_remove` oldest are needed, so partition rather than sort
+                candidates.select_nth_unstable(to_remove - 1);
+                let keys_to_remove = candidates.into_iter().take(to_remove).map(|(_, k)| k);
+                let mut stats = self.stats.lock().map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+                })?;
+                for key in keys_to_remove {
+                    if let Some(entry) = cache.remove(&key) {
+                        stats.tool(&entry.tool_name).cache_evictions += 1;
+                        evicted.push(entry);
+                    }
+                }
+            }
+        }
+
+        // Re-caching a pinned entry keeps it pinned
+        let pinned = pin
+            || cache
+                .get(&cache_key)
+                .is_some_and(|existing| existing.pinned && Self::same_args(existing, args));
+        let now = std::time::Instant::now();
+        cache.insert(
+            cache_key,
+            CachedResult {
+                tool_name: tool_name.to_string(),
+                args: args.to_string(),
+                result: value,
+                timestamp: now.checked_sub(age).unwrap_or(now),
+                pinned,
+                hits: 0,
+                last_used: now,
+            },
+        );
+
+        let mut stats = self.stats.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        stats.tool(tool_name).cache_stores += 1;
+        if let Some(compressed_len) = compressed_len {
+            stats.compression_input_bytes += result.len();
+            stats.compression_output_bytes += compressed_len;
+        }
+        drop(stats);
+        drop(cache);
+
+        self.notify_evicted(evicted)?;
+        Ok(true)
+    }
+
+    // Helper function to pin or unpin an existing, unexpired cache entry
+    fn set_pinned(&self, tool_name: &str, args: &str, pinned: bool) -> PyResult<bool> {
+        let cache_key = self.cache_key(tool_name, args)?;
+        let ttl_secs = self.ttl_for(tool_name)?;
+
+        let mut cache = self.result_cache.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cache lock: {}",
+                e
+            ))
+        })?;
+
+        match cache.get_mut(&cache_key) {
+            Some(entry) if !entry.is_expired(ttl_secs) && Self::same_args(entry, args) => {
+                entry.pinned = pinned;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    // Helper function to hand evicted entries to the on_evict callback, if one is set.
+    // Must be called without holding the cache lock.
+    fn notify_evicted(&self, evicted: Vec<CachedResult>) -> PyResult<()> {
+        if evicted.is_empty() {
+            return Ok(());
+        }
+
+        Python::with_gil(|py| {
+            let callback = {
+                let on_evict = self.on_evict.lock().map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+                })?;
+                match on_evict.as_ref() {
+                    Some(callback) => callback.clone_ref(py),
+                    None => return Ok(()),
+                }
+            };
+
+            for entry in evicted {
+                let result = entry.result.text()?;
+                if let Err(e) = callback.call1(py, (&entry.tool_name, &entry.args, result)) {
+                    log::warn!("on_evict callback failed for tool '{}': {}", entry.tool_name, e);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    // Helper function to look up the TTL for one tool
+    fn ttl_for(&self, tool_name: &str) -> PyResult<u64> {
+        let ttls = self.tool_ttls.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        Ok(self.effective_ttl(&ttls, tool_name))
+    }
+
+    // Helper function to pick a tool's TTL: its `set_tool_ttl` override, else the global TTL
+    fn effective_ttl(&self, ttls: &HashMap<String, u64>, tool_name: &str) -> u64 {
+        ttls.get(tool_name).copied().unwrap_or(self.cache_ttl_secs)
+    }
+
+    // Helper function to put args in the canonical form `parse_args` produces, so key order
+    // and whitespace don't matter; anything that isn't JSON is kept verbatim (it can never
+    // collide textually with valid JSON)
+    fn canonical_args(args: &str) -> std::borrow::Cow<'_, str> {
+        match serde_json::from_str::<serde_json::Value>(args)
+            .ok()
+            .and_then(|value| serde_json::to_string(&value).ok())
+        {
+            Some(canonical) => std::borrow::Cow::Owned(canonical),
+            None => std::borrow::Cow::Borrowed(args),
+        }
+    }
+
+    // Helper function to check that a cache entry was stored for these args. Keys only
+    // carry a hash of the args, so a hit must be confirmed before it's served: two calls
+    // whose hashes collide must not get each other's results.
+    fn same_args(entry: &CachedResult, args: &str) -> bool {
+        entry.args == args || Self::canonical_args(&entry.args) == Self::canonical_args(args)
+    }
+
+    // Helper function to build the cache key for a tool call (private, not exposed to Python):
+    // the length-prefixed tool name and optional `@version`, plus a 64-bit hash of the
+    // canonical args. The length prefix keeps a tool named `a@1` apart from tool `a` at
+    // version `1`. Lookups confirm the args with `same_args` before serving a hit.
+    fn cache_key(&self, tool_name: &str, args: &str) -> PyResult<String> {
+        use std::hash::Hasher;
+
+        let mut hasher = Fnv1aHasher::default();
+        hasher.write(Self::canonical_args(args).as_bytes());
+        let args_hash = hasher.finish();
+
+        let versions = self.tool_versions.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        match versions.get(tool_name) {
+            Some(version) => Ok(format!("{}:{}@{}:{:016x}", tool_name.len(), tool_name, version, args_hash)),
+            None => Ok(format!("{}:{}:{:016x}", tool_name.len(), tool_name, args_hash)),
+        }
+    }
+}
+
+#[pymethods]
+impl RustToolExecutor {
+    /// Create a new tool executor.
+    ///
+    /// `max_cache_value_bytes` caps the size of cacheable results, measured in UTF-8
+    /// bytes; larger results are never cached. With `None` every result is cached.
+    ///
+    /// `max_cache_entries` caps the number of cached results (0 = unlimited; see also
+    /// `set_max_cache_size`). Caching into a full cache first drops expired entries, then
+    /// evicts the least recently used unpinned ones, where storing or serving a result
+    /// counts as a use.
+    ///
+    /// With `compress_cache`, results of at least 512 bytes are gzip-compressed when cached
+    /// and decompressed on every hit, transparently to callers. This trades CPU on each
+    /// store and lookup for memory, and pays off for large, text-heavy outputs such as
+    /// JSON or HTML; results that don't shrink are kept as is. `get_stats` reports the
+    /// bytes compressed and `compression_ratio_percent`, counting each compressed result
+    /// when it is stored in the cache (by `cache_result` or `refresh_expiring`), not
+    /// results the cache refused.
+    #[new]
+    #[pyo3(signature = (max_recursion_depth, cache_ttl_secs=300, max_cache_value_bytes=None, max_cache_entries=1000, compress_cache=false))]
+    pub fn new(
+        max_recursion_depth: usize,
+        cache_ttl_secs: u64,
+        max_cache_value_bytes: Option<usize>,
+        max_cache_entries: usize,
+        compress_cache: bool,
+    ) -> Self {
+        RustToolExecutor {
+            max_recursion_depth,
+            execution_count: Arc::new(Mutex::new(0)),
+            in_flight: Mutex::new(Vec::new()),
+            next_execution_id: std::sync::atomic::AtomicU64::new(0),
+            attempts: Mutex::new(HashMap::new()),
+            result_cache: Arc::new(Mutex::new(HashMap::new())),
+            cache_ttl_secs,
+            tool_ttls: Arc::new(Mutex::new(HashMap::new())),
+            max_cache_size: std::sync::atomic::AtomicUsize::new(max_cache_entries),
+            stats: Arc::new(Mutex::new(ExecutionStats::default())),
+            stats_reset_at: Mutex::new(std::time::Instant::now()),
+            tool_versions: Arc::new(Mutex::new(HashMap::new())),
+            tool_schemas: Arc::new(Mutex::new(HashMap::new())),
+            tool_defaults: Arc::new(Mutex::new(HashMap::new())),
+            max_cache_value_bytes,
+            compress_cache,
+            on_evict: Arc::new(Mutex::new(None)),
+            purge_schedule: Arc::new((Mutex::new(PurgeSchedule::default()), std::sync::Condvar::new())),
+            purge_thread: Mutex::new(None),
+        }
+    }
+
+    /// Set (or with `None`, remove) a callback invoked as `callback(tool_name, args, result)`
+    /// for every entry evicted by the size limit or by TTL expiry, e.g. to persist it to a
+    /// slower cache tier.
+    ///
+    /// The callback runs after the entry has been removed and the cache lock released, so
+    /// it may call back into this executor. Entries evicted by one operation are reported
+    /// in eviction order before that operation returns; a concurrent lookup can miss an
+    /// entry before its callback has run. Exceptions from the callback are logged and
+    /// ignored. `clear_cache` does not invoke the callback.
+    #[pyo3(signature = (callback=None))]
+    pub fn set_on_evict(&self, callback: Option<PyObject>) -> PyResult<()> {
+        let mut on_evict = self.on_evict.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        *on_evict = callback;
+        Ok(())
+    }
+
+    /// Register the current version of a tool.
+    ///
+    /// The version becomes part of the cache key, so bumping it makes entries cached
+    /// under the old version unreachable. Those stale entries are not removed eagerly;
+    /// they linger until TTL expiry or size-based eviction drops them.
+    pub fn register_tool_version(&self, tool_name: &str, version: &str) -> PyResult<()> {
+        let mut versions = self.tool_versions.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        versions.insert(tool_name.to_string(), version.to_string());
+        Ok(())
+    }
+
+    /// Set the TTL for results cached for `tool_name`, overriding the executor-wide
+    /// `cache_ttl_secs` for that tool. The TTL applies whenever an entry's expiry is
+    /// checked, so it also changes when already-cached results of the tool expire.
+    pub fn set_tool_ttl(&self, tool_name: &str, ttl_secs: u64) -> PyResult<()> {
+        let mut ttls = self.tool_ttls.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        ttls.insert(tool_name.to_string(), ttl_secs);
+        Ok(())
+    }
+
+    /// Set the maximum cache size (0 = unlimited)
+    pub fn set_max_cache_size(&self, max_size: usize) -> PyResult<()> {
+        self.max_cache_size.store(max_size, std::sync::atomic::Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Get the current maximum cache size
+    pub fn get_max_cache_size(&self) -> usize {
+        self.max_cache_size.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Validate JSON arguments - returns parsed JSON or error message
+    pub fn validate_args(&self, args_json: &str) -> PyResult<bool> {
+        match serde_json::from_str::<serde_json::Value>(args_json) {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                let mut stats = self.stats.lock().map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+                })?;
+                stats.validation_failures += 1;
+                Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Invalid JSON arguments: {}",
+                    e
+                )))
+            }
+        }
+    }
+
+    /// Register a JSON Schema that `validate_against_schema` checks a tool's arguments
+    /// against, replacing any earlier schema for the tool. Raises `ValueError` if the schema
+    /// isn't a JSON object.
+    ///
+    /// A practical subset of JSON Schema is enforced: `type` (a name or list of names),
+    /// `enum`, `required`, `properties`, `additionalProperties` (false or a schema),
+    /// `items`, `minItems`/`maxItems`, `minLength`/`maxLength` and `minimum`/`maximum`.
+    /// Other keywords (`$ref`, `pattern`, `oneOf`, ...) are accepted but ignored.
+    pub fn register_schema(&self, tool_name: &str, schema_json: &str) -> PyResult<()> {
+        let schema: serde_json::Value = serde_json::from_str(schema_json).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid JSON schema for tool '{}': {}",
+                tool_name, e
+            ))
+        })?;
+        if !schema.is_object() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid JSON schema for tool '{}': expected an object",
+                tool_name
+            )));
+        }
+
+        let mut schemas = self.tool_schemas.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        schemas.insert(tool_name.to_string(), schema);
+        Ok(())
+    }
+
+    /// Validate JSON arguments against the schema registered for `tool_name` with
+    /// `register_schema`. Returns true if they conform; otherwise counts a validation
+    /// failure and raises `ValueError` naming the offending field (e.g. `'$.limit':
+    /// expected integer, got string`). Tools without a schema only get the syntax check
+    /// of `validate_args`.
+    pub fn validate_against_schema(&self, tool_name: &str, args_json: &str) -> PyResult<bool> {
+        let args: serde_json::Value = match serde_json::from_str(args_json) {
+            Ok(args) => args,
+            // Let validate_args count and report the syntax error
+            Err(_) => return self.validate_args(args_json),
+        };
+
+        let violation = {
+            let schemas = self.tool_schemas.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?;
+            schemas
+                .get(tool_name)
+                .and_then(|schema| schema_violation(schema, &args, "$"))
+        };
+
+        match violation {
+            None => Ok(true),
+            Some(violation) => {
+                let mut stats = self.stats.lock().map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+                })?;
+                stats.validation_failures += 1;
+                Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Arguments for tool '{}' do not match its schema: {}",
+                    tool_name, violation
+                )))
+            }
+        }
+    }
+
+    /// Register default arguments for a tool as a JSON object, used by
+    /// `normalize_with_defaults` and replacing any earlier defaults for the tool. Raises
+    /// `ValueError` if `defaults_json` isn't a valid JSON object.
+    pub fn register_defaults(&self, tool_name: &str, defaults_json: &str) -> PyResult<()> {
+        let defaults = match serde_json::from_str::<serde_json::Value>(defaults_json) {
+            Ok(serde_json::Value::Object(defaults)) => defaults,
+            Ok(_) => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Invalid defaults for tool '{}': expected a JSON object",
+                    tool_name
+                )))
+            }
+            Err(e) => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Invalid defaults for tool '{}': {}",
+                    tool_name, e
+                )))
+            }
+        };
+
+        let mut tool_defaults = self.tool_defaults.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        tool_defaults.insert(tool_name.to_string(), defaults);
+        Ok(())
+    }
+
+    /// Fill the arguments' missing keys from the defaults registered with
+    /// `register_defaults` and return the merged arguments as canonical JSON (as from
+    /// `parse_args`), so calls that omit optional arguments share cache entries with calls
+    /// that spell them out.
+    ///
+    /// The merge is shallow: a top-level key the caller supplied is kept whole, even if it is
+    /// an object missing some of the default's nested keys. With `deep=True`, objects present
+    /// on both sides are merged recursively the same way. Values are never type-checked
+    /// against the defaults. Raises `ValueError` for invalid JSON, or for arguments that
+    /// aren't an object when the tool has defaults; without defaults the arguments are only
+    /// canonicalized.
+    #[pyo3(signature = (tool_name, args_json, deep=false))]
+    pub fn normalize_with_defaults(&self, tool_name: &str, args_json: &str, deep: bool) -> PyResult<String> {
+        let mut args: serde_json::Value = serde_json::from_str(args_json).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid JSON: {}",
+                e
+            ))
+        })?;
+
+        {
+            let tool_defaults = self.tool_defaults.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?;
+            if let Some(defaults) = tool_defaults.get(tool_name) {
+                match args.as_object_mut() {
+                    Some(fields) => merge_defaults(fields, defaults, deep),
+                    None => {
+                        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                            "Arguments for tool '{}' must be a JSON object to apply defaults",
+                            tool_name
+                        )))
+                    }
+                }
+            }
+        }
+
+        serde_json::to_string(&args).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to serialize: {}",
+                e
+            ))
+        })
+    }
+
+    /// Parse and normalize JSON arguments for consistent caching
+    pub fn parse_args(&self, args_json: &str) -> PyResult<String> {
+        let value: serde_json::Value = serde_json::from_str(args_json).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid JSON: {}",
+                e
+            ))
+        })?;
+
+        // Re-serialize with sorted keys for consistent cache keys
+        serde_json::to_string(&value).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to serialize: {}",
+                e
+            ))
+        })
+    }
+
+    /// Check if we can execute (recursion depth check)
+    pub fn can_execute(&self) -> PyResult<bool> {
+        let count = self.execution_count.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
+        Ok(*count < self.max_recursion_depth)
+    }
+
+    /// Begin execution - returns a unique execution ID for tracking, whose start time is
+    /// recorded for `check_timeouts`. Besides this executor's `max_recursion_depth`, the
+    /// execution takes a slot under the process-wide limit set by
+    /// `set_global_execution_limit`, released by `end_execution`.
+    pub fn begin_execution(&self, py: Python<'_>, tool_name: &str, args: &str) -> PyResult<String> {
+        let mut count = self.execution_count.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
+
+        if *count >= self.max_recursion_depth {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                "Maximum recursion depth exceeded".to_string(),
+            ));
+        }
+
+        *count += 1;
+        drop(count);
+
+        // Don't hold the count lock while possibly waiting on the global gate
+        if let Err(e) = acquire_global_execution(py) {
+            let mut count = self.execution_count.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire lock: {}",
+                    e
+                ))
+            })?;
+            *count -= 1;
+            return Err(e);
+        }
+
+        // Update stats
+        let mut stats = self.stats.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        stats.total_executions += 1;
+        stats.tool(tool_name).executions += 1;
+        drop(stats);
+
+        // Generate a unique execution ID
+        let sequence = self.next_execution_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let execution_id = format!("{}:{}#{}", tool_name, args.len(), sequence);
+        let mut in_flight = self.in_flight.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        in_flight.push((execution_id.clone(), std::time::Instant::now()));
+        Ok(execution_id)
+    }
+
+    /// End execution - call this after tool completes.
+    ///
+    /// Pass the id from `begin_execution` to end that execution; without one the most
+    /// recently begun execution ends, matching nested calls. Ending an id that isn't in
+    /// flight (already ended, or never begun here) does nothing.
+    #[pyo3(signature = (execution_id=None))]
+    pub fn end_execution(&self, execution_id: Option<&str>) -> PyResult<()> {
+        {
+            let mut in_flight = self.in_flight.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?;
+            match execution_id {
+                Some(execution_id) => match in_flight.iter().position(|(id, _)| id == execution_id) {
+                    Some(index) => {
+                        in_flight.remove(index);
+                    }
+                    None => return Ok(()),
+                },
+                None => {
+                    in_flight.pop();
+                }
+            }
+        }
+
+        let mut count = self.execution_count.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire lock: {}",
+                e
+            ))
+        })?;
+
+        if *count > 0 {
+            *count -= 1;
+            release_global_executions(1);
+        }
+        Ok(())
+    }
+
+    /// Ids of in-flight executions that began more than `max_secs` seconds ago, oldest
+    /// first, so hung tools can be aborted. Checking doesn't end them: they keep their
+    /// recursion-depth and global slots until `end_execution` is called with their id.
+    pub fn check_timeouts(&self, max_secs: u64) -> PyResult<Vec<String>> {
+        let limit = std::time::Duration::from_secs(max_secs);
+        let in_flight = self.in_flight.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        Ok(in_flight
+            .iter()
+            .filter(|(_, started)| started.elapsed() > limit)
+            .map(|(id, _)| id.clone())
+            .collect())
+    }
+
+    /// Record an attempt at `execution_id` and return how many have been recorded for it,
+    /// counting the first call. Counts outlive `end_execution`, so a retried call can keep
+    /// using the id of its first attempt; drop them with `clear_attempts` once it's done.
+    pub fn record_attempt(&self, execution_id: &str) -> PyResult<usize> {
+        let mut attempts = self.attempts.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        let count = attempts.entry(execution_id.to_string()).or_insert(0);
+        *count += 1;
+        Ok(*count)
+    }
+
+    /// Whether `execution_id` may be tried again under a policy of at most `max_retries`
+    /// retries after the first attempt, i.e. whether at most `max_retries` attempts have
+    /// been recorded. An id with no recorded attempts may always be tried.
+    pub fn should_retry(&self, execution_id: &str, max_retries: usize) -> PyResult<bool> {
+        let attempts = self.attempts.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        Ok(attempts.get(execution_id).copied().unwrap_or(0) <= max_retries)
+    }
+
+    /// Forget the attempts recorded for `execution_id`. Returns whether any were recorded.
+    pub fn clear_attempts(&self, execution_id: &str) -> PyResult<bool> {
+        let mut attempts = self.attempts.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        Ok(attempts.remove(execution_id).is_some())
+    }
+
+    /// Get cached result if available and not expired. JSON args match regardless of key
+    /// order and whitespace, so `{"a": 1, "b": 2}` finds a result cached for `{"b":2,"a":1}`.
+    pub fn get_cached(&self, tool_name: &str, args: &str) -> PyResult<Option<String>> {
+        let cache_key = self.cache_key(tool_name, args)?;
+        let ttl_secs = self.ttl_for(tool_name)?;
+
+        let mut cache = self.result_cache.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cache lock: {}",
+                e
+            ))
+        })?;
+
+        // An entry stored for other args under a colliding hash is a miss, and stays cached
+        if let Some(cached) = cache.get_mut(&cache_key).filter(|cached| Self::same_args(cached, args)) {
+            // Check if cache is still valid
+            if !cached.is_expired(ttl_secs) {
+                cached.hits += 1;
+                cached.last_used = std::time::Instant::now();
+                let mut stats = self.stats.lock().map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+                })?;
+                stats.cache_hits += 1;
+                stats.tool(tool_name).cache_hits += 1;
+                return Ok(Some(cached.result.text()?));
+            }
+            // Entry expired - remove it from cache
+            let evicted: Vec<CachedResult> = cache.remove(&cache_key).into_iter().collect();
+            drop(cache);
+            self.notify_evicted(evicted)?;
+        }
+
+        let mut stats = self.stats.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        stats.cache_misses += 1;
+        stats.tool(tool_name).cache_misses += 1;
+        Ok(None)
+    }
+
+    /// Look up many (tool_name, args) pairs at once, returning the cached result or None
+    /// for each, in the same order. Keys are built exactly as in `get_cached`, the cache
+    /// lock is taken once, and every lookup counts as a hit or miss as it would singly.
+    pub fn get_cached_batch(&self, lookups: Vec<(String, String)>) -> PyResult<Vec<Option<String>>> {
+        let keys = lookups
+            .iter()
+            .map(|(tool_name, args)| self.cache_key(tool_name, args))
+            .collect::<PyResult<Vec<String>>>()?;
+        let lookup_ttls: Vec<u64> = {
+            let ttls = self.tool_ttls.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?;
+            lookups.iter().map(|(tool_name, _)| self.effective_ttl(&ttls, tool_name)).collect()
+        };
+
+        let mut cache = self.result_cache.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cache lock: {}",
+                e
+            ))
+        })?;
+
+        let mut results = Vec::with_capacity(keys.len());
+        let mut evicted = Vec::new();
+        for ((cache_key, &ttl_secs), (_, args)) in keys.iter().zip(&lookup_ttls).zip(&lookups) {
+            // As in `get_cached`, an entry stored for other args is a miss
+            match cache.get_mut(cache_key).filter(|cached| Self::same_args(cached, args)) {
+                Some(cached) if !cached.is_expired(ttl_secs) => {
+                    cached.hits += 1;
+                    cached.last_used = std::time::Instant::now();
+                    results.push(Some(cached.result.text()?));
+                }
+                Some(_) => {
+                    // Entry expired - remove it from cache
+                    evicted.extend(cache.remove(cache_key));
+                    results.push(None);
+                }
+                None => results.push(None),
+            }
+        }
+
+        let mut stats = self.stats.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        for ((tool_name, _), result) in lookups.iter().zip(&results) {
+            if result.is_some() {
+                stats.cache_hits += 1;
+                stats.tool(tool_name).cache_hits += 1;
+            } else {
+                stats.cache_misses += 1;
+                stats.tool(tool_name).cache_misses += 1;
+            }
+        }
+        drop(stats);
+        drop(cache);
+
+        self.notify_evicted(evicted)?;
+        Ok(results)
+    }
+
+    /// Store result in cache - returns false if the result was too large to cache, or if
+    /// the cache is full and every entry that could make room is pinned
+    pub fn cache_result(&self, tool_name: &str, args: &str, result: &str) -> PyResult<bool> {
+        self.store_result(tool_name, args, result, std::time::Duration::ZERO, false)
+    }
+
+    /// Refresh cache entries whose remaining TTL is below `within_secs`.
+    ///
+    /// Calls `refetch(tool_name, args) -> str` for each expiring (not yet expired) entry
+    /// and stores the fresh value with a new timestamp, so hot entries never go cold.
+    /// The callback runs without holding the cache lock. If it raises or returns a value
+    /// that can't be cached, the old entry is left in place and the failure is counted in
+    /// `get_stats()["refresh_failures"]`. Returns the number of entries refreshed.
+    pub fn refresh_expiring(&self, py: Python<'_>, within_secs: u64, refetch: PyObject) -> PyResult<usize> {
+        let expiring: Vec<(String, String, String)> = {
+            let cache = self.result_cache.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire cache lock: {}",
+                    e
+                ))
+            })?;
+            let ttls = self.tool_ttls.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?;
+            cache
+                .iter()
+                .filter(|(_, v)| {
+                    // Pinned entries never expire, so they never need refreshing
+                    let age = v.timestamp.elapsed().as_secs();
+                    let ttl_secs = self.effective_ttl(&ttls, &v.tool_name);
+                    !v.pinned && age < ttl_secs && ttl_secs - age < within_secs
+                })
+                .map(|(key, v)| (key.clone(), v.tool_name.clone(), v.args.clone()))
+                .collect()
+        };
+
+        let mut refreshed = 0;
+        let mut failures = 0;
+        let mut compressed_bytes = (0, 0);
+        for (cache_key, tool_name, args) in expiring {
+            let fresh = match refetch
+                .call1(py, (&tool_name, &args))
+                .and_then(|value| value.extract::<String>(py))
+            {
+                Ok(value) if self.max_cache_value_bytes.is_none_or(|max| value.len() <= max) => value,
+                Ok(_) => {
+                    failures += 1;
+                    continue;
+                }
+                Err(e) => {
+                    log::warn!("Cache refresh for tool '{}' failed: {}", tool_name, e);
+                    failures += 1;
+                    continue;
+                }
+            };
+            let value = self.pack_result(&fresh);
+
+            let mut cache = self.result_cache.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire cache lock: {}",
+                    e
+                ))
+            })?;
+            // The entry may have been evicted or cleared while the callback ran
+            if let Some(entry) = cache.get_mut(&cache_key) {
+                if let Some(compressed_len) = value.compressed_len() {
+                    compressed_bytes.0 += fresh.len();
+                    compressed_bytes.1 += compressed_len;
+                }
+                entry.result = value;
+                entry.timestamp = std::time::Instant::now();
+                refreshed += 1;
+            }
+        }
+
+        let mut stats = self.stats.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        stats.refresh_failures += failures;
+        stats.compression_input_bytes += compressed_bytes.0;
+        stats.compression_output_bytes += compressed_bytes.1;
+
+        Ok(refreshed)
+    }
+
+    /// Return the cached result for (tool_name, args), or call `compute() -> str` on a miss,
+    /// cache its result, and return it. Exceptions from `compute` propagate uncached.
+    pub fn get_or_compute(&self, py: Python<'_>, tool_name: &str, args: &str, compute: PyObject) -> PyResult<String> {
+        self.get_or_compute_retry(py, tool_name, args, compute, 1, 0)
+    }
+
+    /// Like `get_or_compute`, but retries a failing `compute()` with exponential backoff.
+    ///
+    /// On a miss, `compute` is called up to `max_attempts` times, sleeping
+    /// `base_delay_ms * 2^(n-1)` milliseconds (with the GIL released) after the n-th
+    /// failure. The first success is cached and returned; if every attempt fails, the last
+    /// exception propagates and nothing is cached. Attempts and failures are counted in
+    /// `get_stats()` as `compute_attempts` and `compute_failures`.
+    pub fn get_or_compute_retry(
+        &self,
+        py: Python<'_>,
+        tool_name: &str,
+        args: &str,
+        compute: PyObject,
+        max_attempts: u32,
+        base_delay_ms: u64,
+    ) -> PyResult<String> {
+        if max_attempts == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "max_attempts must be at least 1",
+            ));
+        }
+
+        if let Some(cached) = self.get_cached(tool_name, args)? {
+            return Ok(cached);
+        }
+
+        let mut attempt = 1;
+        let result = loop {
+            let outcome = compute.call0(py).and_then(|value| value.extract::<String>(py));
+
+            let mut stats = self.stats.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?;
+            stats.compute_attempts += 1;
+            match outcome {
+                Ok(value) => break value,
+                Err(e) => {
+                    stats.compute_failures += 1;
+                    if attempt >= max_attempts {
+                        return Err(e);
+                    }
+                }
+            }
+            drop(stats);
+
+            let delay_ms = base_delay_ms.saturating_mul(1u64 << (attempt - 1).min(32));
+            py.allow_threads(|| std::thread::sleep(std::time::Duration::from_millis(delay_ms)));
+            attempt += 1;
+        };
+
+        self.cache_result(tool_name, args, &result)?;
+        Ok(result)
+    }
+
+    /// Pin a cached entry so it is never evicted by the size limit or expired by TTL until
+    /// `unpin_cache` is called. Returns whether a live entry existed to pin.
+    pub fn pin_cache(&self, tool_name: &str, args: &str) -> PyResult<bool> {
+        self.set_pinned(tool_name, args, true)
+    }
+
+    /// Unpin a cached entry, making it subject to eviction and TTL expiry again (its age is
+    /// counted from when it was cached). Returns whether the entry existed.
+    pub fn unpin_cache(&self, tool_name: &str, args: &str) -> PyResult<bool> {
+        self.set_pinned(tool_name, args, false)
+    }
+
+    /// Get current cache size
+    pub fn get_cache_size(&self) -> PyResult<usize> {
+        let cache = self.result_cache.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cache lock: {}",
+                e
+            ))
+        })?;
+        Ok(cache.len())
+    }
+
+    /// Clear the result cache
+    pub fn clear_cache(&self) -> PyResult<usize> {
+        let mut cache = self.result_cache.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cache lock: {}",
+                e
+            ))
+        })?;
+
+        let count = cache.len();
+        cache.clear();
+        Ok(count)
+    }
+
+    /// Remove every cached result for one tool, e.g. after deploying a new version of it,
+    /// and return how many were removed; other tools' entries stay warm. Entries cached
+    /// under any registered version of the tool are removed, pinned ones included. Like
+    /// `clear_cache`, this doesn't call `on_evict`.
+    pub fn invalidate_tool(&self, tool_name: &str) -> PyResult<usize> {
+        let mut cache = self.result_cache.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cache lock: {}",
+                e
+            ))
+        })?;
+
+        let before = cache.len();
+        cache.retain(|_, entry| entry.tool_name != tool_name);
+        Ok(before - cache.len())
+    }
+
+    /// Actively remove expired entries, returning how many were dropped. Expired entries
+    /// are otherwise only removed when looked up or when the cache fills, so long-running
+    /// processes can call this periodically to release their memory. Removed entries are
+    /// passed to `on_evict`.
+    pub fn cleanup_expired(&self) -> PyResult<usize> {
+        let mut cache = self.result_cache.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cache lock: {}",
+                e
+            ))
+        })?;
+        let ttls = self.tool_ttls.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        let expired_keys: Vec<_> = cache
+            .iter()
+            .filter(|(_, v)| v.is_expired(self.effective_ttl(&ttls, &v.tool_name)))
+            .map(|(k, _)| k.clone())
+            .collect();
+        drop(ttls);
+
+        let evicted: Vec<CachedResult> = expired_keys.iter().filter_map(|key| cache.remove(key)).collect();
+        drop(cache);
+
+        let count = evicted.len();
+        self.notify_evicted(evicted)?;
+        Ok(count)
+    }
+
+    /// Remove unpinned entries that have served fewer than `min_hits` cache hits since they
+    /// were cached, keeping genuinely reused results and dropping one-shots. Returns the
+    /// number removed. Compaction is deliberate pruning, so it does not call `on_evict`.
+    pub fn compact_cache(&self, min_hits: usize) -> PyResult<usize> {
+        let mut cache = self.result_cache.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cache lock: {}",
+                e
+            ))
+        })?;
+
+        let before = cache.len();
+        cache.retain(|_, entry| entry.pinned || entry.hits >= min_hits);
+        Ok(before - cache.len())
+    }
+
+    /// Export the cache as a portable, human-readable JSON manifest.
+    ///
+    /// Each live entry is listed as `{tool, args, result, age_secs, pinned}`, sorted by tool
+    /// and args. Expired entries are omitted, as are pinned ones when `include_pinned` is
+    /// false. Load the manifest into another executor with `import_manifest`; unlike the
+    /// internal cache keys it doesn't depend on registered tool versions.
+    #[pyo3(signature = (include_pinned=true))]
+    pub fn export_manifest(&self, include_pinned: bool) -> PyResult<String> {
+        let cache = self.result_cache.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire cache lock: {}",
+                e
+            ))
+        })?;
+        let ttls = self.tool_ttls.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        let mut entries: Vec<ManifestEntry> = cache
+            .values()
+            .filter(|entry| !entry.is_expired(self.effective_ttl(&ttls, &entry.tool_name)))
+            .filter(|entry| include_pinned || !entry.pinned)
+            .map(|entry| {
+                Ok(ManifestEntry {
+                    tool: entry.tool_name.clone(),
+                    args: entry.args.clone(),
+                    result: entry.result.text()?,
+                    age_secs: entry.timestamp.elapsed().as_secs(),
+                    pinned: entry.pinned,
+                })
+            })
+            .collect::<PyResult<_>>()?;
+        entries.sort_by(|a, b| (&a.tool, &a.args).cmp(&(&b.tool, &b.args)));
+
+        serde_json::to_string_pretty(&CacheManifest {
+            format_version: FORMAT_VERSION,
+            entries,
+        })
+        .map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to serialize to JSON: {}",
+                e
+            ))
+        })
+    }
+
+    /// Prefill the cache from a manifest written by `export_manifest`, returning how many
+    /// entries were cached.
+    ///
+    /// Each entry keeps its age, so it expires when it would have in the exporting
+    /// executor (measured against this executor's TTL for the tool), and unpinned entries that are
+    /// already that old are skipped. Pinned entries stay pinned. Entries go through the
+    /// same path as `cache_result`, including the size limits and `on_evict`.
+    pub fn import_manifest(&self, json: &str) -> PyResult<usize> {
+        let manifest: CacheManifest = parse_versioned(json)?;
+
+        let mut imported = 0;
+        for entry in manifest.entries {
+            if !entry.pinned && entry.age_secs >= self.ttl_for(&entry.tool)? {
+                continue;
+            }
+            let age = std::time::Duration::from_secs(entry.age_secs);
+            if self.store_result(&entry.tool, &entry.args, &entry.result, age, entry.pinned)? {
+                imported += 1;
+            }
+        }
+        Ok(imported)
+    }
+
+    /// Save the live cache entries to `path` so a restarted process can start warm with
+    /// `import_cache`, returning how many entries were written.
+    ///
+    /// Each entry records the wall-clock time it expires under this executor's TTL for its
+    /// tool, since the monotonic clock behind TTLs doesn't survive a restart. Expired
+    /// entries are skipped; pinned ones are kept and stay pinned. The file is written to a
+    /// temporary path and renamed into place, so a crash never leaves a partial file.
+    pub fn export_cache(&self, path: &str) -> PyResult<usize> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let (json, exported) = {
+            let cache = self.result_cache.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire cache lock: {}",
+                    e
+                ))
+            })?;
+            let ttls = self.tool_ttls.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?;
+
+            let entries = cache
+                .values()
+                .filter_map(|entry| {
+                    let ttl_secs = self.effective_ttl(&ttls, &entry.tool_name);
+                    if entry.is_expired(ttl_secs) {
+                        return None;
+                    }
+                    let remaining = ttl_secs.saturating_sub(entry.timestamp.elapsed().as_secs());
+                    Some(entry.result.text().map(|result| CacheFileEntry {
+                        tool: entry.tool_name.clone(),
+                        args: entry.args.clone(),
+                        result,
+                        expires_at: now.saturating_add(remaining),
+                        pinned: entry.pinned,
+                    }))
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            let exported = entries.len();
+
+            let json = serde_json::to_string(&CacheFile {
+                format_version: FORMAT_VERSION,
+                entries,
+            })
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to serialize to JSON: {}",
+                    e
+                ))
+            })?;
+            (json, exported)
+        };
+
+        let tmp_path = format!("{}.tmp", path);
+        std::fs::write(&tmp_path, json)
+            .and_then(|_| std::fs::rename(&tmp_path, path))
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                    "Failed to write cache file '{}': {}",
+                    path, e
+                ))
+            })?;
+        Ok(exported)
+    }
+
+    /// Load the entries in a file written by `export_cache`, returning how many were
+    /// cached.
+    ///
+    /// Unpinned entries already past their recorded expiry are skipped. The rest expire at
+    /// that recorded time, or sooner if this executor's TTL for the tool is shorter; pinned
+    /// entries stay pinned. Entries go through the same path as `cache_result`, including
+    /// the size limits, compression and `on_evict`.
+    pub fn import_cache(&self, path: &str) -> PyResult<usize> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                "Failed to read cache file '{}': {}",
+                path, e
+            ))
+        })?;
+        let file: CacheFile = parse_versioned(&contents)?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let mut imported = 0;
+        for entry in file.entries {
+            let remaining = entry.expires_at.saturating_sub(now);
+            if !entry.pinned && remaining == 0 {
+                continue;
+            }
+            // Backdate the entry so it expires `remaining` seconds from now
+            let age = std::time::Duration::from_secs(self.ttl_for(&entry.tool)?.saturating_sub(remaining));
+            if self.store_result(&entry.tool, &entry.args, &entry.result, age, entry.pinned)? {
+                imported += 1;
+            }
+        }
+        Ok(imported)
+    }
+
+    /// Purge every unpinned cached entry for `tool_name` every `interval_secs` seconds,
+    /// regardless of TTL. The first purge runs one interval from now; scheduling a tool
+    /// that already has a purge replaces its interval.
+    ///
+    /// All schedules are driven by a single background thread, started by the first call,
+    /// that sleeps until the nearest deadline and is woken early whenever a schedule is
+    /// added or cancelled. It only takes the cache lock while sweeping and never touches
+    /// Python, so purges do not call `on_evict`. Dropping the executor stops the thread.
+    pub fn schedule_purge(&self, tool_name: &str, interval_secs: u64) -> PyResult<()> {
+        if interval_secs == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "interval_secs must be positive",
+            ));
+        }
+
+        let (schedule, wakeup) = &*self.purge_schedule;
+        {
+            let mut schedule = schedule.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?;
+            let interval = std::time::Duration::from_secs(interval_secs);
+            schedule
+                .purges
+                .insert(tool_name.to_string(), (interval, std::time::Instant::now() + interval));
+        }
+        wakeup.notify_all();
+
+        let mut purge_thread = self.purge_thread.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        if purge_thread.is_none() {
+            let schedule = Arc::clone(&self.purge_schedule);
+            let cache = Arc::clone(&self.result_cache);
+            let handle = std::thread::Builder::new()
+                .name("tool-cache-purge".to_string())
+                .spawn(move || Self::run_purges(schedule, cache))
+                .map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                        "Failed to start purge thread: {}",
+                        e
+                    ))
+                })?;
+            *purge_thread = Some(handle);
+        }
+        Ok(())
+    }
+
+    /// Stop the scheduled purge for `tool_name`, returning whether one was scheduled
+    pub fn cancel_purge(&self, tool_name: &str) -> PyResult<bool> {
+        let (schedule, wakeup) = &*self.purge_schedule;
+        let removed = schedule
+            .lock()
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?
+            .purges
+            .remove(tool_name)
+            .is_some();
+        wakeup.notify_all();
+        Ok(removed)
+    }
+
+    /// Get execution statistics. `secs_since_reset` is the whole seconds the counters have
+    /// been accumulating, since `reset_stats` or executor creation, for computing rates.
+    pub fn get_stats(&self) -> PyResult<HashMap<String, usize>> {
+        let pinned_entries = self
+            .result_cache
+            .lock()
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire cache lock: {}",
+                    e
+                ))
+            })?
+            .values()
+            .filter(|entry| entry.pinned)
+            .count();
+
+        let stats = self.stats.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        let mut result = HashMap::new();
+        result.insert("total_executions".to_string(), stats.total_executions);
+        result.insert("pinned_entries".to_string(), pinned_entries);
+        result.insert("cache_hits".to_string(), stats.cache_hits);
+        result.insert("cache_misses".to_string(), stats.cache_misses);
+        result.insert("validation_failures".to_string(), stats.validation_failures);
+        result.insert("skipped_oversized".to_string(), stats.skipped_oversized);
+        result.insert("refresh_failures".to_string(), stats.refresh_failures);
+        result.insert("compute_attempts".to_string(), stats.compute_attempts);
+        result.insert("compute_failures".to_string(), stats.compute_failures);
+        result.insert("compression_input_bytes".to_string(), stats.compression_input_bytes);
+        result.insert("compression_output_bytes".to_string(), stats.compression_output_bytes);
+
+        let reset_at = self.stats_reset_at.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        result.insert("secs_since_reset".to_string(), reset_at.elapsed().as_secs() as usize);
+
+        // Calculate cache hit rate
+        let total_cache_lookups = stats.cache_hits + stats.cache_misses;
+        if let Some(hit_rate) = (stats.cache_hits * 100).checked_div(total_cache_lookups) {
+            result.insert("cache_hit_rate_percent".to_string(), hit_rate);
+        }
+
+        // Original size as a percentage of compressed size, e.g. 400 for 4:1
+        if let Some(ratio) = (stats.compression_input_bytes * 100).checked_div(stats.compression_output_bytes) {
+            result.insert("compression_ratio_percent".to_string(), ratio);
+        }
+
+        Ok(result)
+    }
+
+    /// Zero every execution counter, global and per-tool, to start a fresh measurement
+    /// window; `secs_since_reset` restarts too. Cached results are untouched. Snapshots from
+    /// `stats_snapshot` taken before the reset diff against the zeroed counters, so their
+    /// deltas can be negative.
+    pub fn reset_stats(&self) -> PyResult<()> {
+        let mut stats = self.stats.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        let mut reset_at = self.stats_reset_at.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        *stats = ExecutionStats::default();
+        *reset_at = std::time::Instant::now();
+        Ok(())
+    }
+
+    /// Get per-tool cache counters (hits, misses, stores, evictions) for cache tuning.
+    ///
+    /// Evictions count entries dropped to make room under the size limit; a tool with
+    /// many stores and evictions but few hits is thrashing the cache.
+    pub fn cache_analytics(&self) -> PyResult<HashMap<String, HashMap<String, usize>>> {
+        let stats = self.stats.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        Ok(stats
+            .per_tool
+            .iter()
+            .map(|(tool_name, tool_stats)| {
+                let mut counters = HashMap::new();
+                counters.insert("hits".to_string(), tool_stats.cache_hits);
+                counters.insert("misses".to_string(), tool_stats.cache_misses);
+                counters.insert("stores".to_string(), tool_stats.cache_stores);
+                counters.insert("evictions".to_string(), tool_stats.cache_evictions);
+                (tool_name.clone(), counters)
+            })
+            .collect())
+    }
+
+    /// Get one tool's counters: `executions` (calls to `begin_execution` that ran) and the
+    /// cache's `hits`, `misses`, `stores` and `evictions` for it. A tool with no recorded
+    /// activity reports zeros.
+    pub fn get_tool_stats(&self, tool_name: &str) -> PyResult<HashMap<String, usize>> {
+        let stats = self.stats.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        Ok(stats.per_tool.get(tool_name).cloned().unwrap_or_default().counters())
+    }
+
+    /// Get `get_tool_stats` for every tool with recorded activity, keyed by tool name
+    pub fn get_all_tool_stats(&self) -> PyResult<HashMap<String, HashMap<String, usize>>> {
+        let stats = self.stats.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        Ok(stats
+            .per_tool
+            .iter()
+            .map(|(tool_name, tool_stats)| (tool_name.clone(), tool_stats.counters()))
+            .collect())
+    }
+
+    /// Capture every execution counter, global and per-tool, as a JSON snapshot to pass to
+    /// `diff_stats` later. Taking a snapshot doesn't reset anything.
+    pub fn stats_snapshot(&self) -> PyResult<String> {
+        let stats = self.stats.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        serde_json::to_string(&StatsSnapshot {
+            format_version: FORMAT_VERSION,
+            stats: stats.clone(),
+        })
+        .map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to serialize to JSON: {}",
+                e
+            ))
+        })
+    }
+
+    /// Compute how much each counter has changed since `snapshot_json` (from
+    /// `stats_snapshot`) was taken, for measuring one window of a workload.
+    ///
+    /// Keys are the `get_stats` counter names plus `per_tool.<tool>.<counter>` for the
+    /// `get_tool_stats` counters; tools first seen after the snapshot count from zero.
+    /// `cache_hit_rate_percent` is the hit rate within the window, present only if the
+    /// window had cache lookups.
+    pub fn diff_stats(&self, snapshot_json: &str) -> PyResult<HashMap<String, f64>> {
+        let snapshot: StatsSnapshot = parse_versioned(snapshot_json)?;
+        let before = snapshot.stats.counters();
+        let now = self
+            .stats
+            .lock()
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?
+            .counters();
+
+        let mut delta: HashMap<String, f64> = now
+            .iter()
+            .map(|(name, value)| {
+                let previous = before.get(name).copied().unwrap_or(0);
+                (name.clone(), *value as f64 - previous as f64)
+            })
+            .collect();
+        for (name, value) in &before {
+            delta.entry(name.clone()).or_insert(-(*value as f64));
+        }
+
+        let lookups = delta["cache_hits"] + delta["cache_misses"];
+        if lookups > 0.0 {
+            let hit_rate = delta["cache_hits"] * 100.0 / lookups;
+            delta.insert("cache_hit_rate_percent".to_string(), hit_rate);
+        }
+
+        Ok(delta)
+    }
+
+    /// Batch validate multiple tool argument sets
+    pub fn batch_validate(&self, args_list: Vec<String>) -> PyResult<Vec<bool>> {
+        args_list
+            .iter()
+            .map(|args| {
+                serde_json::from_str::<serde_json::Value>(args)
+                    .map(|_| true)
+                    .unwrap_or(false)
+            })
+            .map(Ok)
+            .collect()
+    }
+
+    /// Batch validate multiple tool argument sets, returning `(valid, error)` for each in
+    /// order, where `error` is the JSON parse error for invalid entries. Unlike
+    /// `batch_validate`, each invalid entry counts as a validation failure, as it would
+    /// with `validate_args`.
+    pub fn batch_validate_detailed(&self, args_list: Vec<String>) -> PyResult<Vec<(bool, Option<String>)>> {
+        let results: Vec<(bool, Option<String>)> = args_list
+            .iter()
+            .map(|args| match serde_json::from_str::<serde_json::Value>(args) {
+                Ok(_) => (true, None),
+                Err(e) => (false, Some(format!("Invalid JSON arguments: {}", e))),
+            })
+            .collect();
+
+        let failures = results.iter().filter(|(valid, _)| !valid).count();
+        if failures > 0 {
+            let mut stats = self.stats.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?;
+            stats.validation_failures += failures;
+        }
+
+        Ok(results)
+    }
+}
+
+/// Last timestamp handed out by `AgentMessage::now`, so auto-stamped messages strictly increase
+static LAST_MESSAGE_TIMESTAMP: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// A message structure for serialization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[pyclass]
+pub struct AgentMessage {
+    #[pyo3(get, set)]
+    pub id: String,
+    #[pyo3(get, set)]
+    pub sender: String,
+    #[pyo3(get, set)]
+    pub recipient: String,
+    #[pyo3(get, set)]
+    pub content: String,
+    #[pyo3(get, set)]
+    pub timestamp: u64,
+    /// Id of the message this one replies to, if any
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pub reply_to: Option<String>,
+}
+
+#[pymethods]
+impl AgentMessage {
+    #[new]
+    #[pyo3(signature = (id, sender, recipient, content, timestamp, reply_to=None))]
+    pub fn new(id: &str, sender: &str, recipient: &str, content: &str, timestamp: u64, reply_to: Option<String>) -> Self {
+        AgentMessage {
+            id: id.to_string(),
+            sender: sender.to_string(),
+            recipient: recipient.to_string(),
+            content: content.to_string(),
+            timestamp,
+            reply_to,
+        }
+    }
+
+    /// Create a message stamped with the current time in milliseconds since the Unix epoch.
+    ///
+    /// Timestamps from `now` are strictly increasing across the process: a message created
+    /// in the same millisecond as (or, after a clock step back, earlier than) the previous
+    /// one gets the previous timestamp + 1, so creation order is always preserved. Use the
+    /// regular constructor to supply explicit timestamps for replay or tests.
+    #[staticmethod]
+    #[pyo3(signature = (id, sender, recipient, content, reply_to=None))]
+    pub fn now(id: &str, sender: &str, recipient: &str, content: &str, reply_to: Option<String>) -> Self {
+        let clock_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        let previous = LAST_MESSAGE_TIMESTAMP
+            .fetch_update(
+                std::sync::atomic::Ordering::SeqCst,
+                std::sync::atomic::Ordering::SeqCst,
+                |last| Some(clock_ms.max(last + 1)),
+            )
+            .unwrap_or_else(|last| last);
+        let timestamp = clock_ms.max(previous + 1);
+
+        AgentMessage::new(id, sender, recipient, content, timestamp, reply_to)
+    }
+
+    pub fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(self).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to serialize to JSON: {}",
+                e
+            ))
+        })
+    }
+
+    #[staticmethod]
+    pub fn from_json(json_str: &str) -> PyResult<AgentMessage> {
+        serde_json::from_str(json_str).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to deserialize from JSON: {}",
+                e
+            ))
+        })
+    }
+
+    /// Messages are equal when their ids are, whatever their other fields, so a set of
+    /// messages holds one per id. Only `==` and `!=` are supported.
+    fn __richcmp__(&self, other: &Bound<'_, PyAny>, op: pyo3::basic::CompareOp) -> PyResult<PyObject> {
+        use pyo3::IntoPyObjectExt;
+
+        let py = other.py();
+        let Ok(other) = other.downcast::<AgentMessage>() else {
+            return Ok(py.NotImplemented());
+        };
+        let same_id = self.id == other.borrow().id;
+        match op {
+            pyo3::basic::CompareOp::Eq => same_id.into_py_any(py),
+            pyo3::basic::CompareOp::Ne => (!same_id).into_py_any(py),
+            _ => Ok(py.NotImplemented()),
+        }
+    }
+
+    /// Hash of the id, consistent with `==`. Changing the id of a message held in a set
+    /// or dict breaks the lookup, as for any mutable key.
+    fn __hash__(&self) -> u64 {
+        use std::hash::Hasher;
+
+        let mut hasher = Fnv1aHasher::default();
+        hasher.write(self.id.as_bytes());
+        hasher.finish()
+    }
+
+    /// Stable hex digest of `sender`, `recipient` and `content`, ignoring id, timestamp
+    /// and `reply_to`, for spotting the same content sent under different ids. It doesn't
+    /// change across processes or releases, so it can be stored.
+    pub fn content_fingerprint(&self) -> String {
+        use std::hash::Hasher;
+
+        let mut hasher = Fnv1aHasher::default();
+        // Length-prefix each field so adjacent values can't run together
+        for field in [&self.sender, &self.recipient, &self.content] {
+            hasher.write(&(field.len() as u64).to_le_bytes());
+            hasher.write(field.as_bytes());
+        }
+        format!("{:016x}", hasher.finish())
+    }
+
+    /// Build a dict with the message's fields directly, without a JSON round trip. Keys
+    /// match `to_json`, so `from_dict(json.loads(msg.to_json()))` works too.
+    pub fn to_dict(&self, py: Python<'_>) -> PyResult<Py<PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("id", &self.id)?;
+        dict.set_item("sender", &self.sender)?;
+        dict.set_item("recipient", &self.recipient)?;
+        dict.set_item("content", &self.content)?;
+        dict.set_item("timestamp", self.timestamp)?;
+        dict.set_item("reply_to", &self.reply_to)?;
+        Ok(dict.unbind())
+    }
+
+    /// Create a message from a dict shaped like `to_dict` output. `reply_to` may be
+    /// missing or `None`; any other missing field raises `ValueError`, and extra keys are
+    /// ignored.
+    #[staticmethod]
+    pub fn from_dict(dict: &Bound<'_, PyDict>) -> PyResult<AgentMessage> {
+        let field = |name: &str| {
+            dict.get_item(name)?.ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Missing field '{}' in message dict",
+                    name
+                ))
+            })
+        };
+
+        Ok(AgentMessage {
+            id: field("id")?.extract()?,
+            sender: field("sender")?.extract()?,
+            recipient: field("recipient")?.extract()?,
+            content: field("content")?.extract()?,
+            timestamp: field("timestamp")?.extract()?,
+            reply_to: match dict.get_item("reply_to")? {
+                Some(reply_to) => reply_to.extract()?,
+                None => None,
+            },
+        })
+    }
+
+    /// Serialize to MessagePack, a compact binary alternative to `to_json` for high-volume
+    /// transport. Fields are encoded by name, so messages written before a field was added
+    /// still load, and the timestamp stays an exact integer.
+    pub fn to_msgpack(&self) -> PyResult<Vec<u8>> {
+        rmp_serde::to_vec_named(self).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to serialize to MessagePack: {}",
+                e
+            ))
+        })
+    }
+
+    #[staticmethod]
+    pub fn from_msgpack(data: &[u8]) -> PyResult<AgentMessage> {
+        rmp_serde::from_slice(data).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to deserialize from MessagePack: {}",
+                e
+            ))
+        })
+    }
+}
+
+/// Serialized form of a conversation
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ConversationData {
+    #[serde(default = "default_format_version")]
+    format_version: u64,
+    messages: Vec<AgentMessage>,
+}
+
+/// An ordered conversation of agent messages
+#[pyclass]
+pub struct Conversation {
+    messages: Arc<Mutex<Vec<AgentMessage>>>,
+}
+
+impl Default for Conversation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[pymethods]
+impl Conversation {
+    #[new]
+    pub fn new() -> Self {
+        Conversation {
+            messages: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Append a message to the conversation
+    pub fn add(&self, msg: AgentMessage) -> PyResult<()> {
+        let mut messages = self.messages.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        messages.push(msg);
+        Ok(())
+    }
+
+    /// Get all messages in the order they were added
+    pub fn messages(&self) -> PyResult<Vec<AgentMessage>> {
+        let messages = self.messages.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        Ok(messages.clone())
+    }
+
+    /// Get the causal chain rooted at a message: the root plus every message that
+    /// (transitively) replies to it, in conversation order
+    pub fn thread(&self, root_id: &str) -> PyResult<Vec<AgentMessage>> {
+        let messages = self.messages.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        if !messages.iter().any(|msg| msg.id == root_id) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Message '{}' not found",
+                root_id
+            )));
+        }
+
+        let mut in_thread = std::collections::HashSet::new();
+        in_thread.insert(root_id.to_string());
+
+        // Replies may be added before their parents, so repeat until no new links are found
+        loop {
+            let mut changed = false;
+            for msg in messages.iter() {
+                if let Some(parent) = &msg.reply_to {
+                    if in_thread.contains(parent) && in_thread.insert(msg.id.clone()) {
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        Ok(messages
+            .iter()
+            .filter(|msg| in_thread.contains(&msg.id))
+            .cloned()
+            .collect())
+    }
+
+    pub fn to_json(&self) -> PyResult<String> {
+        let messages = self.messages.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        let data = ConversationData {
+            format_version: FORMAT_VERSION,
+            messages: messages.clone(),
+        };
+        serde_json::to_string(&data).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to serialize to JSON: {}",
+                e
+            ))
+        })
+    }
+
+    /// Restore a conversation from `to_json` output; raises `ValueError` for a newer
+    /// format version
+    #[staticmethod]
+    pub fn from_json(json_str: &str) -> PyResult<Conversation> {
+        let data: ConversationData = parse_versioned(json_str)?;
+
+        Ok(Conversation {
+            messages: Arc::new(Mutex::new(data.messages)),
+        })
+    }
+}
+
+/// Task state for tracking execution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum TaskState {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    /// Stopped while running by `cancel_all_running`
+    Cancelled,
+}
+
+impl TaskState {
+    fn is_terminal(&self) -> bool {
+        matches!(self, TaskState::Completed | TaskState::Failed | TaskState::Cancelled)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            TaskState::Pending => "pending",
+            TaskState::Running => "running",
+            TaskState::Completed => "completed",
+            TaskState::Failed => "failed",
+            TaskState::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// A task with dependencies and state tracking
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TaskInfo {
+    dependencies: Vec<String>,
+    state: TaskState,
+    result: Option<String>,
+    error: Option<String>,
+    /// Structured failure details (error type, code, retryable, ...) from `mark_failed`
+    #[serde(default)]
+    error_details: Option<HashMap<String, String>>,
+}
+
+/// Successful task ids and (task_id, error) failures from one execution wave
+type WaveOutcome = (Vec<String>, Vec<(String, String)>);
+
+/// Serialized form of the task graph, used by `to_json` and checkpoints
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TaskGraphSnapshot {
+    #[serde(default = "default_format_version")]
+    format_version: u64,
+    tasks: HashMap<String, TaskInfo>,
+}
+
+/// A concurrent task executor with dependency tracking
+#[pyclass]
+pub struct RustTaskExecutor {
+    // Use Box wrapped in Option - allows us to take ownership in Drop
+    // PyO3 manages the Python object lifetime
+    runtime: Option<Box<tokio::runtime::Runtime>>,
+    tasks: Arc<Mutex<HashMap<String, TaskInfo>>>,
+    stats: Arc<Mutex<TaskExecutionStats>>,
+    /// Longest allowed dependency chain, in dependency edges (None = unlimited)
+    max_depth: Option<usize>,
+}
+
+impl Drop for RustTaskExecutor {
+    fn drop(&mut self) {
+        // Explicitly shutdown the runtime to clean up worker threads
+        // This prevents memory leaks when many executors are created
+        // By taking the Box and letting it go out of scope, the Runtime's destructor
+        // will properly shutdown all worker threads
+        if let Some(runtime) = self.runtime.take() {
+            drop(runtime);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct TaskExecutionStats {
+    tasks_scheduled: usize,
+    tasks_completed: usize,
+    tasks_failed: usize,
+    tasks_cancelled: usize,
+    total_execution_time_ms: u64,
+}
+
+impl RustTaskExecutor {
+    // Helper function to find the longest dependency chain starting at a task, returned
+    // as [task_id, dependency, dependency's dependency, ...]. Unregistered dependencies
+    // end a chain, and edges back onto the current path (cycles) are ignored.
+    fn longest_chain(
+        tasks: &HashMap<String, TaskInfo>,
+        task_id: &str,
+        memo: &mut HashMap<String, Vec<String>>,
+        on_path: &mut std::collections::HashSet<String>,
+    ) -> Vec<String> {
+        if let Some(chain) = memo.get(task_id) {
+            return chain.clone();
+        }
+
+        on_path.insert(task_id.to_string());
+        let mut longest_tail: Vec<String> = Vec::new();
+        if let Some(task) = tasks.get(task_id) {
+            for dep_id in &task.dependencies {
+                if on_path.contains(dep_id) {
+                    continue;
+                }
+                let tail = Self::longest_chain(tasks, dep_id, memo, on_path);
+                if tail.len() > longest_tail.len() {
+                    longest_tail = tail;
+                }
+            }
+        }
+        on_path.remove(task_id);
+
+        let mut chain = vec![task_id.to_string()];
+        chain.extend(longest_tail);
+        memo.insert(task_id.to_string(), chain.clone());
+        chain
+    }
+
+    // Helper function to encode a namespaced task id as "namespace:task_id"
+    fn namespaced_id(namespace: &str, task_id: &str) -> PyResult<String> {
+        if namespace.is_empty() || namespace.contains(':') {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid task namespace '{}': must be non-empty and must not contain ':'",
+                namespace
+            )));
+        }
+        Ok(format!("{}:{}", namespace, task_id))
+    }
+
+    // Helper function to run one wave of tasks on the runtime, returning the successful
+    // task ids and (task_id, error) pairs for failures when not failing fast
+    fn run_wave(&self, tasks: Vec<String>, fail_fast: bool) -> PyResult<WaveOutcome> {
+        let runtime = self.runtime.as_ref().expect("Runtime not initialized");
+
+        Python::with_gil(|py| {
+            py.allow_threads(|| {
+                runtime.block_on(async {
+                    let mut handles = Vec::new();
+
+                    for task in tasks {
+                        let task_str = task.clone();
+                        let handle = tokio::spawn(async move {
+                            // Return the task ID - actual execution happens in Python
+                            task_str
+                        });
+                        handles.push((task, handle));
+                    }
+
+                    let mut results = Vec::new();
+                    let mut failures = Vec::new();
+                    for (task_id, handle) in handles {
+                        match handle.await {
+                            Ok(result) => results.push(result),
+                            Err(e) => {
+                                if fail_fast {
+                                    return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                                        format!("Task execution failed: {}", e),
+                                    ));
+                                }
+                                failures.push((task_id, format!("Task execution failed: {}", e)));
+                            }
+                        }
+                    }
+
+                    Ok((results, failures))
+                })
+            })
+        })
+    }
+
+    // Helper function to write the task graph to a checkpoint file (via a temp file
+    // and rename, so a crash mid-write never leaves a truncated checkpoint)
+    fn write_checkpoint(&self, path: &str) -> PyResult<()> {
+        let json = self.to_json()?;
+        let tmp_path = format!("{}.tmp", path);
+        std::fs::write(&tmp_path, json)
+            .and_then(|_| std::fs::rename(&tmp_path, path))
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                    "Failed to write checkpoint '{}': {}",
+                    path, e
+                ))
+            })
+    }
+
+    // Helper function to mark a registered, non-terminal task as failed
+    fn record_failure(&self, task_id: &str, error: &str) -> PyResult<()> {
+        let mut tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        if let Some(task) = tasks.get_mut(task_id) {
+            if !task.state.is_terminal() {
+                task.state = TaskState::Failed;
+                task.error = Some(error.to_string());
+
+                let mut stats = self.stats.lock().map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+                })?;
+                stats.tasks_failed += 1;
+            }
+        } else {
+            log::warn!("Unregistered task '{}' failed: {}", task_id, error);
+        }
+        Ok(())
+    }
+
+    // Helper function to parse a JSON error payload into string fields. Non-string values
+    // keep their JSON text; anything that isn't a JSON object is kept as {"message": payload}.
+    fn parse_error_details(payload: &str) -> HashMap<String, String> {
+        match serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(payload) {
+            Ok(fields) => fields
+                .into_iter()
+                .map(|(key, value)| match value {
+                    serde_json::Value::String(text) => (key, text),
+                    other => (key, other.to_string()),
+                })
+                .collect(),
+            Err(_) => HashMap::from([("message".to_string(), payload.to_string())]),
+        }
+    }
+
+    // Helper function to reject transitions out of a terminal state unless forced
+    fn check_transition(task_id: &str, from: &TaskState, to: &TaskState, force: bool) -> PyResult<()> {
+        if from.is_terminal() && !force {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Task '{}' is already {} and cannot be marked {} (use force=True to override)",
+                task_id,
+                from.name(),
+                to.name()
+            )));
+        }
+        Ok(())
+    }
+
+    // Helper function to check whether a task is pending with all dependencies completed
+    fn is_ready(tasks: &HashMap<String, TaskInfo>, task: &TaskInfo) -> bool {
+        task.state == TaskState::Pending && Self::dependencies_satisfied(tasks, task)
+    }
+
+    // Helper function to check whether every dependency of a task has completed
+    fn dependencies_satisfied(tasks: &HashMap<String, TaskInfo>, task: &TaskInfo) -> bool {
+        task.dependencies.iter().all(|dep_id| {
+            tasks
+                .get(dep_id)
+                .map(|dep_task| dep_task.state == TaskState::Completed)
+                .unwrap_or(false)
+        })
+    }
+
+    // Helper function to collect the failed, cancelled or missing dependencies that (transitively)
+    // prevent a task from ever becoming ready
+    fn collect_permanent_blockers(
+        tasks: &HashMap<String, TaskInfo>,
+        task_id: &str,
+        visited: &mut std::collections::HashSet<String>,
+        blockers: &mut Vec<String>,
+    ) {
+        if !visited.insert(task_id.to_string()) {
+            return;
+        }
+
+        if let Some(task) = tasks.get(task_id) {
+            for dep_id in &task.dependencies {
+                match tasks.get(dep_id) {
+                    None => {
+                        if !blockers.contains(dep_id) {
+                            blockers.push(dep_id.clone());
+                        }
+                    }
+                    Some(dep_task) => match dep_task.state {
+                        TaskState::Failed | TaskState::Cancelled => {
+                            if !blockers.contains(dep_id) {
+                                blockers.push(dep_id.clone());
+                            }
+                        }
+                        TaskState::Pending => {
+                            Self::collect_permanent_blockers(tasks, dep_id, visited, blockers)
+                        }
+                        TaskState::Running | TaskState::Completed => {}
+                    },
+                }
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl RustTaskExecutor {
+    /// Create an executor.
+    ///
+    /// `max_depth` limits how long dependency chains may get, counted in dependency edges
+    /// (a task with no dependencies has depth 0, a task depending only on it depth 1).
+    /// Registering a task that would create a deeper chain raises `ValueError`. `None`
+    /// (the default) enforces no limit.
+    ///
+    /// The executor normally runs on a multi-thread Tokio runtime. If that can't be built
+    /// (e.g. in a container that can't spawn threads), it logs a warning and falls back to
+    /// a current-thread runtime, where each wave's tasks run one at a time on the calling
+    /// thread instead of concurrently. `runtime_flavor` reports which runtime is in use.
+    #[new]
+    #[pyo3(signature = (max_depth=None))]
+    pub fn new(max_depth: Option<usize>) -> PyResult<Self> {
+        let runtime = match tokio::runtime::Builder::new_multi_thread().enable_all().build() {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                log::warn!(
+                    "Failed to create multi-thread Tokio runtime ({}), falling back to current-thread",
+                    e
+                );
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .map_err(|e| {
+                        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                            "Failed to create Tokio runtime: {}",
+                            e
+                        ))
+                    })?
+            }
+        };
+
+        Ok(RustTaskExecutor {
+            runtime: Some(Box::new(runtime)),
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+            stats: Arc::new(Mutex::new(TaskExecutionStats::default())),
+            max_depth,
+        })
+    }
+
+    /// Which Tokio runtime the executor runs on: "multi_thread", or "current_thread" after
+    /// falling back because the multi-thread runtime couldn't be built
+    pub fn runtime_flavor(&self) -> String {
+        let runtime = self.runtime.as_ref().expect("Runtime not initialized");
+        match runtime.handle().runtime_flavor() {
+            tokio::runtime::RuntimeFlavor::MultiThread => "multi_thread",
+            tokio::runtime::RuntimeFlavor::CurrentThread => "current_thread",
+            _ => "unknown",
+        }
+        .to_string()
+    }
+
+    /// Register a task with optional dependencies
+    pub fn register_task(&self, task_id: &str, dependencies: Vec<String>) -> PyResult<()> {
+        let mut tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        let previous = tasks.insert(
+            task_id.to_string(),
+            TaskInfo {
+                dependencies,
+                state: TaskState::Pending,
+                result: None,
+                error: None,
+                error_details: None,
+            },
+        );
+
+        if let Some(max_depth) = self.max_depth {
+            // The new task can deepen chains through tasks that already depend on it,
+            // so check every task rather than just this one
+            let mut memo = HashMap::new();
+            let mut deepest: Vec<String> = Vec::new();
+            for id in tasks.keys() {
+                let chain = Self::longest_chain(&tasks, id, &mut memo, &mut std::collections::HashSet::new());
+                if chain.len() > deepest.len() {
+                    deepest = chain;
+                }
+            }
+
+            let depth = deepest.len().saturating_sub(1);
+            if depth > max_depth {
+                match previous {
+                    Some(info) => tasks.insert(task_id.to_string(), info),
+                    None => tasks.remove(task_id),
+                };
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Registering task '{}' would create a dependency chain of depth {} (max_depth {}): {}",
+                    task_id,
+                    depth,
+                    max_depth,
+                    deepest.join(" -> ")
+                )));
+            }
+        }
+
+        let mut stats = self.stats.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        stats.tasks_scheduled += 1;
+
+        Ok(())
+    }
+
+    /// Register a task under a namespace, with dependencies given as (namespace, task_id).
+    ///
+    /// Namespaced tasks are stored under the combined id `"namespace:task_id"` (see
+    /// `qualify_id`), and every other method (`can_execute`, `mark_completed`,
+    /// `get_result`, ...) accepts that combined id. Tasks registered with `register_task`
+    /// live in the default namespace and keep their plain ids, so the two APIs can share
+    /// one executor. Namespaces must be non-empty and must not contain ':'.
+    pub fn register_task_ns(
+        &self,
+        namespace: &str,
+        task_id: &str,
+        dependencies: Vec<(String, String)>,
+    ) -> PyResult<()> {
+        let task_id = Self::namespaced_id(namespace, task_id)?;
+        let dependencies = dependencies
+            .iter()
+            .map(|(dep_namespace, dep_id)| Self::namespaced_id(dep_namespace, dep_id))
+            .collect::<PyResult<Vec<String>>>()?;
+        self.register_task(&task_id, dependencies)
+    }
+
+    /// Combined id for a namespaced task, as accepted by the other task methods
+    #[staticmethod]
+    pub fn qualify_id(namespace: &str, task_id: &str) -> PyResult<String> {
+        Self::namespaced_id(namespace, task_id)
+    }
+
+    /// Check if a task's dependencies are all completed
+    pub fn can_execute(&self, task_id: &str) -> PyResult<bool> {
+        let tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        if let Some(task) = tasks.get(task_id) {
+            for dep_id in &task.dependencies {
+                if let Some(dep_task) = tasks.get(dep_id) {
+                    if dep_task.state != TaskState::Completed {
+                        return Ok(false);
+                    }
+                } else {
+                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                        "Dependency task '{}' not found",
+                        dep_id
+                    )));
+                }
+            }
+            Ok(true)
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Task '{}' not found",
+                task_id
+            )))
+        }
+    }
+
+    /// Get all tasks that are ready to execute (dependencies satisfied)
+    pub fn get_ready_tasks(&self) -> PyResult<Vec<String>> {
+        let tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        let ready = tasks
+            .iter()
+            .filter(|(_, task)| Self::is_ready(&tasks, task))
+            .map(|(task_id, _)| task_id.clone())
+            .collect();
+
+        Ok(ready)
+    }
+
+    /// Whether at least one task is ready to run (same rule as `get_ready_tasks`), without
+    /// building the list - a cheap check for scheduler loops
+    pub fn has_ready_tasks(&self) -> PyResult<bool> {
+        let tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        Ok(tasks.values().any(|task| Self::is_ready(&tasks, task)))
+    }
+
+    /// Check whether the task graph is stuck: tasks are pending, but none is ready
+    /// and none is running, so no further progress is possible
+    pub fn is_deadlocked(&self) -> PyResult<bool> {
+        let tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        let mut has_pending = false;
+        for task in tasks.values() {
+            match task.state {
+                TaskState::Running => return Ok(false),
+                TaskState::Pending => {
+                    if Self::dependencies_satisfied(&tasks, task) {
+                        return Ok(false);
+                    }
+                    has_pending = true;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(has_pending)
+    }
+
+    /// Map each stuck pending task to the dependencies blocking it.
+    ///
+    /// Failed and missing dependencies are permanent blocks and are reported even when
+    /// they sit further up the chain. If the graph is deadlocked and a task has no such
+    /// blocker (e.g. a dependency cycle), its unsatisfied direct dependencies are listed.
+    pub fn deadlock_reason(&self) -> PyResult<HashMap<String, Vec<String>>> {
+        let deadlocked = self.is_deadlocked()?;
+
+        let tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        let mut reasons = HashMap::new();
+        for (task_id, task) in tasks.iter() {
+            if task.state != TaskState::Pending || Self::dependencies_satisfied(&tasks, task) {
+                continue;
+            }
+
+            let mut blockers = Vec::new();
+            let mut visited = std::collections::HashSet::new();
+            Self::collect_permanent_blockers(&tasks, task_id, &mut visited, &mut blockers);
+
+            if blockers.is_empty() && deadlocked {
+                blockers = task
+                    .dependencies
+                    .iter()
+                    .filter(|dep_id| {
+                        tasks
+                            .get(*dep_id)
+                            .map(|dep_task| dep_task.state != TaskState::Completed)
+                            .unwrap_or(true)
+                    })
+                    .cloned()
+                    .collect();
+            }
+
+            if !blockers.is_empty() {
+                reasons.insert(task_id.clone(), blockers);
+            }
+        }
+
+        Ok(reasons)
+    }
+
+    /// Mark a task as started.
+    ///
+    /// Follows the same terminal-state policy as `mark_completed`: a completed, failed or
+    /// cancelled task can't be started again (e.g. by a late-arriving worker) unless
+    /// `force=True`, and a `ValueError` is raised instead.
+    #[pyo3(signature = (task_id, force=false))]
+    pub fn mark_started(&self, task_id: &str, force: bool) -> PyResult<()> {
+        let mut tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        if let Some(task) = tasks.get_mut(task_id) {
+            Self::check_transition(task_id, &task.state, &TaskState::Running, force)?;
+            task.state = TaskState::Running;
+            Ok(())
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Task '{}' not found",
+                task_id
+            )))
+        }
+    }
+
+    /// Mark a task as completed with a result.
+    ///
+    /// Tasks move Pending -> Running -> terminal (completed/failed/cancelled). Once a task is
+    /// terminal it cannot be marked again (e.g. a late result after a timeout) and a
+    /// `ValueError` is raised; pass `force=True` to override this for recovery.
+    #[pyo3(signature = (task_id, result, force=false))]
+    pub fn mark_completed(&self, task_id: &str, result: &str, force: bool) -> PyResult<()> {
+        let mut tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        if let Some(task) = tasks.get_mut(task_id) {
+            Self::check_transition(task_id, &task.state, &TaskState::Completed, force)?;
+            task.state = TaskState::Completed;
+            task.result = Some(result.to_string());
+
+            let mut stats = self.stats.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?;
+            stats.tasks_completed += 1;
+
+            Ok(())
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Task '{}' not found",
+                task_id
+            )))
+        }
+    }
+
+    /// Mark a task as completed with a structured result, stored as the dict's JSON
+    /// encoding (as produced by Python's `json.dumps`), so `get_result` and persistence
+    /// see an ordinary string. Read it back with `get_result_json`. Raises `ValueError`
+    /// if the dict isn't JSON-serializable; otherwise behaves like `mark_completed`.
+    #[pyo3(signature = (task_id, result, force=false))]
+    pub fn mark_completed_json(&self, task_id: &str, result: Bound<'_, PyDict>, force: bool) -> PyResult<()> {
+        let json: String = PyModule::import(result.py(), "json")?
+            .call_method1("dumps", (&result,))
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Result for task '{}' is not JSON-serializable: {}",
+                    task_id, e
+                ))
+            })?
+            .extract()?;
+        self.mark_completed(task_id, &json, force)
+    }
+
+    /// Mark a task as failed with an error message.
+    ///
+    /// Follows the same terminal-state policy as `mark_completed`. `details` is an optional
+    /// JSON object (e.g. `{"type": "Timeout", "retryable": true}`) stored alongside the
+    /// message and returned by `get_error_details`; a payload that isn't a valid JSON
+    /// object is kept verbatim under the `"message"` key.
+    #[pyo3(signature = (task_id, error, force=false, details=None))]
+    pub fn mark_failed(&self, task_id: &str, error: &str, force: bool, details: Option<&str>) -> PyResult<()> {
+        let mut tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        if let Some(task) = tasks.get_mut(task_id) {
+            Self::check_transition(task_id, &task.state, &TaskState::Failed, force)?;
+            task.state = TaskState::Failed;
+            task.error = Some(error.to_string());
+            task.error_details = details.map(Self::parse_error_details);
+
+            let mut stats = self.stats.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?;
+            stats.tasks_failed += 1;
+
+            Ok(())
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Task '{}' not found",
+                task_id
+            )))
+        }
+    }
+
+    /// Cancel every running task, e.g. to tear down a partially executed graph, and return
+    /// the cancelled ids (sorted). Cancelled is terminal like completed and failed, so a
+    /// late `mark_completed` or `mark_failed` raises unless forced, and tasks depending on a
+    /// cancelled task never become ready. Pending, completed and failed tasks are untouched.
+    pub fn cancel_all_running(&self) -> PyResult<Vec<String>> {
+        let mut tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        let mut cancelled: Vec<String> = tasks
+            .iter_mut()
+            .filter(|(_, task)| task.state == TaskState::Running)
+            .map(|(task_id, task)| {
+                task.state = TaskState::Cancelled;
+                task_id.clone()
+            })
+            .collect();
+        cancelled.sort();
+
+        let mut stats = self.stats.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+        stats.tasks_cancelled += cancelled.len();
+
+        Ok(cancelled)
+    }
+
+    /// Get the result of a completed task parsed from JSON into a Python object, e.g. one
+    /// stored by `mark_completed_json`. Returns `None` if the task has no result, and raises
+    /// `ValueError` if the stored result isn't valid JSON.
+    pub fn get_result_json(&self, py: Python<'_>, task_id: &str) -> PyResult<Option<PyObject>> {
+        let Some(result) = self.get_result(task_id)? else {
+            return Ok(None);
+        };
+
+        let value = PyModule::import(py, "json")?
+            .call_method1("loads", (result,))
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Result of task '{}' is not valid JSON: {}",
+                    task_id, e
+                ))
+            })?;
+        Ok(Some(value.unbind()))
+    }
+
+    /// Get the result of a completed task
+    pub fn get_result(&self, task_id: &str) -> PyResult<Option<String>> {
+        let tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        if let Some(task) = tasks.get(task_id) {
+            Ok(task.result.clone())
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Task '{}' not found",
+                task_id
+            )))
+        }
+    }
+
+    /// Get the error message of a failed task
+    pub fn get_error(&self, task_id: &str) -> PyResult<Option<String>> {
+        let tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        if let Some(task) = tasks.get(task_id) {
+            Ok(task.error.clone())
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Task '{}' not found",
+                task_id
+            )))
+        }
+    }
+
+    /// Get the structured details recorded by `mark_failed`, or None if none were given
+    pub fn get_error_details(&self, task_id: &str) -> PyResult<Option<HashMap<String, String>>> {
+        let tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        if let Some(task) = tasks.get(task_id) {
+            Ok(task.error_details.clone())
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Task '{}' not found",
+                task_id
+            )))
+        }
+    }
+
+    /// Get topological sort order for task execution
+    pub fn get_execution_order(&self) -> PyResult<Vec<String>> {
+        let tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        // Kahn's algorithm for topological sort
+        let mut in_degree: HashMap<String, usize> = HashMap::new();
+        let mut adj_list: HashMap<String, Vec<String>> = HashMap::new();
+
+        // Initialize
+        for (task_id, task) in tasks.iter() {
+            in_degree.entry(task_id.clone()).or_insert(0);
+            adj_list.entry(task_id.clone()).or_default();
+
+            for dep_id in &task.dependencies {
+                *in_degree.entry(task_id.clone()).or_insert(0) += 1;
+                adj_list
+                    .entry(dep_id.clone())
+                    .or_default()
+                    .push(task_id.clone());
+            }
+        }
+
+        // Find all nodes with no incoming edges
+        let mut queue: Vec<String> = in_degree
+            .iter()
+            .filter(|(_, &deg)| deg == 0)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        let mut result = Vec::new();
+
+        while let Some(node) = queue.pop() {
+            result.push(node.clone());
+
+            if let Some(neighbors) = adj_list.get(&node) {
+                for neighbor in neighbors {
+                    if let Some(deg) = in_degree.get_mut(neighbor) {
+                        *deg -= 1;
+                        if *deg == 0 {
+                            queue.push(neighbor.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        // Check for cycles
+        if result.len() != tasks.len() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "Circular dependency detected in tasks",
+            ));
+        }
+
+        Ok(result)
+    }
+
+    /// Execute multiple independent tasks concurrently and aggregate results.
+    ///
+    /// With `fail_fast=True` (default) the first task failure aborts the batch with a
+    /// `RuntimeError`. With `fail_fast=False` every task is awaited and only the
+    /// successful task ids are returned; each failed task that is registered with this
+    /// executor is marked failed with the error message (see `get_error`), so the batch
+    /// makes partial progress instead of losing everything to one failure.
+    ///
+    /// Every task id is run and returned in order unless `skip_completed=True`, which
+    /// skips (and leaves out of the result) tasks already marked completed in this
+    /// executor, so a batch restored via `from_json` only reruns unfinished work. When
+    /// `checkpoint_path` is set and `checkpoint_every > 0`, tasks run in waves of
+    /// `checkpoint_every` and the full task graph (see `to_json`) is written to
+    /// `checkpoint_path` after each wave, between waves so running tasks aren't blocked.
+    #[pyo3(signature = (tasks, fail_fast=true, checkpoint_path=None, checkpoint_every=0, skip_completed=false))]
+    pub fn execute_concurrent_tasks(
+        &self,
+        tasks: Vec<String>,
+        fail_fast: bool,
+        checkpoint_path: Option<String>,
+        checkpoint_every: usize,
+        skip_completed: bool,
+    ) -> PyResult<Vec<String>> {
+        let start_time = std::time::Instant::now();
+
+        let pending: Vec<String> = if skip_completed {
+            let registered = self.tasks.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?;
+            tasks
+                .into_iter()
+                .filter(|task_id| {
+                    registered
+                        .get(task_id)
+                        .map(|task| task.state != TaskState::Completed)
+                        .unwrap_or(true)
+                })
+                .collect()
+        } else {
+            tasks
+        };
+
+        let wave_size = match checkpoint_path {
+            Some(_) if checkpoint_every > 0 => checkpoint_every,
+            _ => pending.len().max(1),
+        };
+
+        let mut results = Vec::new();
+        let mut outcome = Ok(());
+        for wave in pending.chunks(wave_size) {
+            let (wave_results, failures) = match self.run_wave(wave.to_vec(), fail_fast) {
+                Ok(wave_outcome) => wave_outcome,
+                Err(e) => {
+                    outcome = Err(e);
+                    break;
+                }
+            };
+
+            results.extend(wave_results);
+            for (task_id, error) in failures {
+                self.record_failure(&task_id, &error)?;
+            }
+
+            if let (Some(path), true) = (&checkpoint_path, checkpoint_every > 0) {
+                self.write_checkpoint(path)?;
+            }
+        }
+
+        // Update stats
+        let elapsed_ms = start_time.elapsed().as_millis() as u64;
+        if let Ok(mut stats) = self.stats.lock() {
+            stats.total_execution_time_ms += elapsed_ms;
+        }
+
+        outcome.map(|_| results)
+    }
+
+    /// Serialize the full task graph (dependencies, states, results, errors) to JSON
+    pub fn to_json(&self) -> PyResult<String> {
+        let tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        let snapshot = TaskGraphSnapshot {
+            format_version: FORMAT_VERSION,
+            tasks: tasks.clone(),
+        };
+        serde_json::to_string(&snapshot).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to serialize to JSON: {}",
+                e
+            ))
+        })
+    }
+
+    /// Restore an executor from `to_json` output (e.g. a checkpoint file's contents).
+    ///
+    /// Tasks that were running when the snapshot was taken are reset to pending so they
+    /// run again; completed tasks keep their results and are not rerun by
+    /// `execute_concurrent_tasks(..., skip_completed=True)`. Snapshots from a newer format
+    /// version raise `ValueError`.
+    #[staticmethod]
+    pub fn from_json(json_str: &str) -> PyResult<RustTaskExecutor> {
+        let mut snapshot: TaskGraphSnapshot = parse_versioned(json_str)?;
+
+        for task in snapshot.tasks.values_mut() {
+            if task.state == TaskState::Running {
+                task.state = TaskState::Pending;
+            }
+        }
+
+        let executor = RustTaskExecutor::new(None)?;
+        {
+            let mut stats = executor.stats.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+            })?;
+            stats.tasks_scheduled = snapshot.tasks.len();
+        }
+        *executor.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })? = snapshot.tasks;
+
+        Ok(executor)
+    }
+
+    /// Get execution statistics
+    pub fn get_stats(&self) -> PyResult<HashMap<String, usize>> {
+        let stats = self.stats.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        let mut result = HashMap::new();
+        result.insert("tasks_scheduled".to_string(), stats.tasks_scheduled);
+        result.insert("tasks_completed".to_string(), stats.tasks_completed);
+        result.insert("tasks_failed".to_string(), stats.tasks_failed);
+        result.insert("tasks_cancelled".to_string(), stats.tasks_cancelled);
+        result.insert(
+            "total_execution_time_ms".to_string(),
+            stats.total_execution_time_ms as usize,
+        );
+
+        Ok(result)
+    }
+
+    /// Clear all tasks
+    pub fn clear(&self) -> PyResult<()> {
+        let mut tasks = self.tasks.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        tasks.clear();
+        Ok(())
+    }
+}
+
+/// Maximum number of slow queries kept for `slow_queries`
+const SLOW_QUERY_LOG_CAPACITY: usize = 100;
+
+/// Maximum length of query text recorded in the slow query log
+const SLOW_QUERY_TEXT_LIMIT: usize = 200;
+
+/// Columns `materialize_ranks` is allowed to write into. Non-matching rows are cleared
+/// to NULL, so only nullable rank columns belong here (not `score`, which readers
+/// expect to be non-null).
+const MATERIALIZABLE_RANK_COLUMNS: &[&str] = &["materialized_rank"];
+
+/// How `search_memories` ranks FTS5 matches
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FtsRanker {
+    /// Call the `bm25()` auxiliary function directly
+    Bm25,
+    /// Use the FTS5 `rank` hidden column, i.e. the table's configured ranking function
+    Rank,
+}
+
+impl FtsRanker {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "bm25" => Ok(FtsRanker::Bm25),
+            "rank" => Ok(FtsRanker::Rank),
+            other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid ranker '{}': expected 'bm25' or 'rank'",
+                other
+            ))),
+        }
+    }
+
+    fn sql(self) -> &'static str {
+        match self {
+            FtsRanker::Bm25 => "bm25(long_term_memories_fts)",
+            FtsRanker::Rank => "fts.rank",
+        }
+    }
+}
+
+/// A query that exceeded the slow query threshold
+#[derive(Debug, Clone)]
+struct SlowQuery {
+    query: String,
+    param_count: usize,
+    duration_ms: f64,
+}
+
+/// Open write transaction that batched `insert_memory` calls accumulate into
+struct WriteBatch {
+    /// Dedicated connection, outside the pool so the batch never holds a pooled slot
+    conn: rusqlite::Connection,
+    /// Commit once this many rows are pending
+    max_rows: usize,
+    /// Commit once the oldest pending row is this old (checked on each insert)
+    max_age: Option<std::time::Duration>,
+    /// Rows inserted since the transaction began
+    pending: usize,
+    /// When the open transaction began (None = no transaction open)
+    started: Option<std::time::Instant>,
+}
+
+impl WriteBatch {
+    // Commit the open transaction, if any, returning how many rows it held
+    fn commit(&mut self) -> rusqlite::Result<usize> {
+        if self.started.is_none() {
+            return Ok(0);
+        }
+        self.conn.execute_batch("COMMIT")?;
+        let committed = self.pending;
+        self.pending = 0;
+        self.started = None;
+        Ok(committed)
+    }
+}
+
+/// A high-performance SQLite wrapper with FTS5 support
+#[pyclass]
+pub struct RustSQLiteWrapper {
+    connection_pool: Arc<Mutex<r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>>>,
+    /// Threshold above which queries are logged as slow (None = disabled)
+    slow_query_ms: Option<u64>,
+    /// Ring buffer of the most recent slow queries
+    slow_queries: Arc<Mutex<std::collections::VecDeque<SlowQuery>>>,
+    /// Per-query time limit enforced via SQLite's progress handler (None = disabled)
+    query_timeout_ms: Option<u64>,
+    /// Read-only replica pools that reads are spread across (empty = read from the primary)
+    replica_pools: Vec<r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>>,
+    /// Round-robin counter selecting the next replica pool
+    next_replica: std::sync::atomic::AtomicUsize,
+    /// Pending batched inserts (None = every insert commits immediately)
+    write_batch: Option<Mutex<WriteBatch>>,
+}
+
+impl Drop for RustSQLiteWrapper {
+    fn drop(&mut self) {
+        // Dropping a connection mid-transaction rolls it back, so commit what's pending
+        if let Some(batch) = self.write_batch.as_ref() {
+            if let Ok(mut batch) = batch.lock() {
+                if let Err(e) = batch.commit() {
+                    log::warn!("Failed to commit pending writes on drop: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Number of SQLite VM instructions between query deadline checks
+const QUERY_TIMEOUT_CHECK_INTERVAL: i32 = 1000;
+
+impl RustSQLiteWrapper {
+    // Helper function to quote a term as an FTS5 string literal, so any FTS syntax
+    // inside it (operators, column filters, `*`) is matched literally
+    fn fts_quote(term: &str) -> String {
+        format!("\"{}\"", term.replace('"', "\"\""))
+    }
+
+    // Helper function to install a fresh query deadline on a checked-out connection.
+    // Every checkout re-arms the handler, so a stale deadline never outlives its query.
+    fn arm_query_timeout(&self, conn: &rusqlite::Connection) -> PyResult<()> {
+        if let Some(timeout_ms) = self.query_timeout_ms {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_millis(timeout_ms);
+            conn.progress_handler(
+                QUERY_TIMEOUT_CHECK_INTERVAL,
+                Some(move || std::time::Instant::now() >= deadline),
+            ).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to install query timeout: {}",
+                    e
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    // Helper function to commit any batched inserts. Called before every other write, since
+    // the batch's open transaction would otherwise make pooled writers wait on its lock.
+    fn flush_pending(&self) -> PyResult<usize> {
+        let batch = match self.write_batch.as_ref() {
+            Some(batch) => batch,
+            None => return Ok(0),
+        };
+        let mut batch = batch.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire write batch lock: {}",
+                e
+            ))
+        })?;
+        batch.commit().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to commit batched writes: {}",
+                e
+            ))
+        })
+    }
+
+    // Helper function to check out a connection for a read-only query: the next replica
+    // in round-robin order, or the primary when the wrapper has no replicas
+    fn read_connection(&self) -> PyResult<r2d2::PooledConnection<r2d2_sqlite::SqliteConnectionManager>> {
+        let conn = if self.replica_pools.is_empty() {
+            let pool = self.connection_pool.lock().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to acquire pool lock: {}",
+                    e
+                ))
+            })?;
+            pool.get()
+        } else {
+            let index = self.next_replica.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+                % self.replica_pools.len();
+            self.replica_pools[index].get()
+        }
+        .map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to get connection: {}",
+                e
+            ))
+        })?;
+        self.arm_query_timeout(&conn)?;
+        Ok(conn)
+    }
+
+    // Helper function to convert a query error, reporting progress-handler aborts clearly
+    fn query_error(context: &str, e: rusqlite::Error) -> PyErr {
+        if e.sqlite_error_code() == Some(rusqlite::ErrorCode::OperationInterrupted) {
+            return PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("query exceeded time limit");
+        }
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("{}: {}", context, e))
+    }
+
+    // Helper function to unpack one `execute_batch` item into its query and string params
+    fn batch_item(item: &Bound<'_, PyAny>) -> PyResult<(String, Vec<(String, String)>)> {
+        let tuple = item.downcast::<PyTuple>()?;
+        if tuple.len() != 2 {
+            return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
+                "expected 2 items, got {}",
+                tuple.len()
+            )));
+        }
+        let query: String = tuple.get_item(0)?.extract()?;
+        let params = tuple.get_item(1)?;
+        let params = params.downcast::<PyDict>()?;
+
+        let mut param_values: Vec<(String, String)> = Vec::new();
+        for (key, value) in params.iter() {
+            param_values.push((key.extract()?, value.extract()?));
+        }
+        Ok((query, param_values))
+    }
+
+    // Helper function to add a column to an existing table if it is missing (schema migration)
+    fn ensure_column(conn: &rusqlite::Connection, table: &str, column: &str, declaration: &str) -> rusqlite::Result<()> {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let columns = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        if !columns.iter().any(|c| c == column) {
+            conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, declaration), [])?;
+        }
+        Ok(())
+    }
+
+    // Helper function to record a query in the slow query log if it exceeded the threshold
+    fn record_query_time(&self, query: &str, param_count: usize, elapsed: std::time::Duration) {
+        let threshold_ms = match self.slow_query_ms {
+            Some(threshold_ms) => threshold_ms,
+            None => return,
+        };
+
+        let duration_ms = elapsed.as_secs_f64() * 1000.0;
+        if duration_ms <= threshold_ms as f64 {
+            return;
+        }
+
+        let mut query_text: String = query.chars().take(SLOW_QUERY_TEXT_LIMIT).collect();
+        if query_text.len() < query.len() {
+            query_text.push_str("...");
+        }
+
+        log::warn!(
+            "Slow query ({:.2} ms, {} params): {}",
+            duration_ms,
+            param_count,
+            query_text
+        );
+
+        if let Ok(mut slow_queries) = self.slow_queries.lock() {
+            if slow_queries.len() >= SLOW_QUERY_LOG_CAPACITY {
+                slow_queries.pop_front();
+            }
+            slow_queries.push_back(SlowQuery {
+                query: query_text,
+                param_count,
+                duration_ms,
+            });
+        }
+    }
+}
+
+#[pymethods]
+impl RustSQLiteWrapper {
+    /// Create a new wrapper over the given database file.
+    ///
+    /// `fts_prefix` declares FTS5 prefix indexes (e.g. `[2, 3]`) so `term*` queries on
+    /// short prefixes are fast; each length must be between 1 and 999, as FTS5 requires.
+    /// The FTS table is only created if it does not exist yet, so changing this for an
+    /// existing database requires dropping and rebuilding the `long_term_memories_fts`
+    /// table.
+    ///
+    /// `slow_query_ms` enables the slow query log: `execute_query`, `execute_update` and
+    /// `search_memories` calls slower than the threshold are logged as warnings and kept
+    /// for retrieval via `slow_queries`.
+    ///
+    /// `query_timeout_ms` aborts any statement still running after that many
+    /// milliseconds with a `RuntimeError` ("query exceeded time limit"). This bounds
+    /// execution time, unlike a busy timeout which only bounds waiting for locks.
+    ///
+    /// `write_batch_rows` and/or `write_batch_ms` turn on write batching: `insert_memory`
+    /// adds rows to one open transaction that is committed once that many rows are pending,
+    /// once the batch is that old (checked when the next row arrives), on `flush_writes`,
+    /// or when the wrapper is dropped. Committing many rows at once avoids an fsync per
+    /// row, which greatly speeds up streaming ingestion. The tradeoff is durability: rows
+    /// not yet committed are lost if the process crashes, so call `flush_writes` at points
+    /// where data must be safe. Uncommitted rows are also invisible to `search_memories`,
+    /// `execute_query` and other reads until flushed. Every other write method flushes the
+    /// batch first.
+    #[new]
+    #[pyo3(signature = (db_path, pool_size, fts_prefix=None, slow_query_ms=None, query_timeout_ms=None, write_batch_rows=None, write_batch_ms=None))]
+    pub fn new(
+        db_path: &str,
+        pool_size: u32,
+        fts_prefix: Option<Vec<usize>>,
+        slow_query_ms: Option<u64>,
+        query_timeout_ms: Option<u64>,
+        write_batch_rows: Option<usize>,
+        write_batch_ms: Option<u64>,
+    ) -> PyResult<Self> {
+        if write_batch_rows == Some(0) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "write_batch_rows must be positive",
+            ));
+        }
+
+        let prefix_option = match fts_prefix {
+            Some(lengths) if !lengths.is_empty() => {
+                if let Some(length) = lengths.iter().find(|l| !(1..=999).contains(*l)) {
+                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                        "FTS5 prefix lengths must be between 1 and 999, got {}",
+                        length
+                    )));
+                }
+                let lengths: Vec<String> = lengths.iter().map(|l| l.to_string()).collect();
+                format!(",\n                    prefix='{}'", lengths.join(" "))
+            }
+            _ => String::new(),
+        };
+
+        let manager = r2d2_sqlite::SqliteConnectionManager::file(db_path);
+        let pool = r2d2::Pool::builder()
+            .max_size(pool_size)
+            .build(manager)
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to create connection pool: {}",
+                    e
+                ))
+            })?;
+
+        // Initialize database schema with FTS5 for full-text search
+        {
+            let conn = pool.get().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to get connection: {}",
+                    e
+                ))
+            })?;
+
+            // Main table for long-term memories
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS long_term_memories (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    task_description TEXT,
+                    metadata TEXT,
+                    datetime TEXT,
+                    score REAL,
+                    materialized_rank REAL,
+                    tag TEXT
+                )",
+                [],
+            ).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to create table: {}",
+                    e
+                ))
+            })?;
+
+            // Databases created by older versions lack the newer nullable columns
+            Self::ensure_column(&conn, "long_term_memories", "materialized_rank", "REAL")
+                .and_then(|_| Self::ensure_column(&conn, "long_term_memories", "tag", "TEXT"))
+                .map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                        "Failed to migrate table: {}",
+                        e
+                    ))
+                })?;
+
+            // Exact-match lookups by tag (get_by_tag) go through this index, not FTS
+            conn.execute(
+                "CREATE INDEX IF NOT EXISTS long_term_memories_tag_idx ON long_term_memories(tag)",
+                [],
+            ).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to create tag index: {}",
+                    e
+                ))
+            })?;
+
+            // FTS5 virtual table for full-text search
+            conn.execute(
+                &format!(
+                    "CREATE VIRTUAL TABLE IF NOT EXISTS long_term_memories_fts USING fts5(
+                    task_description,
+                    metadata,
+                    content='long_term_memories',
+                    content_rowid='id'{}
+                )",
+                    prefix_option
+                ),
+                [],
+            ).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to create FTS5 table: {}",
+                    e
+                ))
+            })?;
+
+            // Create triggers to keep FTS5 in sync
+            conn.execute_batch(
+                "CREATE TRIGGER IF NOT EXISTS long_term_memories_ai AFTER INSERT ON long_term_memories BEGIN
+                    INSERT INTO long_term_memories_fts(rowid, task_description, metadata)
+                    VALUES (new.id, new.task_description, new.metadata);
+                END;
+                CREATE TRIGGER IF NOT EXISTS long_term_memories_ad AFTER DELETE ON long_term_memories BEGIN
+                    INSERT INTO long_term_memories_fts(long_term_memories_fts, rowid, task_description, metadata)
+                    VALUES('delete', old.id, old.task_description, old.metadata);
+                END;
+                CREATE TRIGGER IF NOT EXISTS long_term_memories_au AFTER UPDATE ON long_term_memories BEGIN
+                    INSERT INTO long_term_memories_fts(long_term_memories_fts, rowid, task_description, metadata)
+                    VALUES('delete', old.id, old.task_description, old.metadata);
+                    INSERT INTO long_term_memories_fts(rowid, task_description, metadata)
+                    VALUES (new.id, new.task_description, new.metadata);
+                END;"
+            ).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to create FTS5 triggers: {}",
+                    e
+                ))
+            })?;
+        }
+
+        let write_batch = if write_batch_rows.is_some() || write_batch_ms.is_some() {
+            let conn = rusqlite::Connection::open(db_path).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to open write batch connection: {}",
+                    e
+                ))
+            })?;
+            Some(Mutex::new(WriteBatch {
+                conn,
+                max_rows: write_batch_rows.unwrap_or(usize::MAX),
+                max_age: write_batch_ms.map(std::time::Duration::from_millis),
+                pending: 0,
+                started: None,
+            }))
+        } else {
+            None
+        };
+
+        Ok(RustSQLiteWrapper {
+            connection_pool: Arc::new(Mutex::new(pool)),
+            slow_query_ms,
+            slow_queries: Arc::new(Mutex::new(std::collections::VecDeque::new())),
+            query_timeout_ms,
+            replica_pools: Vec::new(),
+            next_replica: std::sync::atomic::AtomicUsize::new(0),
+            write_batch,
+        })
+    }
+
+    /// Create a wrapper that writes to `primary` and spreads reads across `replicas`.
+    ///
+    /// `insert_memory` and `execute_update` (and every other write) go to the primary,
+    /// which is initialized like `new`. `search_memories` and `execute_query` rotate
+    /// round-robin across one pool per replica file. Replicas are opened read-only and
+    /// must already contain the schema.
+    ///
+    /// Nothing here copies data between files: replica freshness depends entirely on the
+    /// external replication mechanism, so a read may not see a write that just succeeded.
+    #[staticmethod]
+    pub fn with_replicas(primary: &str, replicas: Vec<String>, pool_size: u32) -> PyResult<Self> {
+        let mut wrapper = Self::new(primary, pool_size, None, None, None, None, None)?;
+
+        for replica in &replicas {
+            let manager = r2d2_sqlite::SqliteConnectionManager::file(replica).with_flags(
+                rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY
+                    | rusqlite::OpenFlags::SQLITE_OPEN_URI
+                    | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            );
+            let pool = r2d2::Pool::builder()
+                .max_size(pool_size)
+                .build(manager)
+                .map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                        "Failed to create connection pool for replica '{}': {}",
+                        replica, e
+                    ))
+                })?;
+            wrapper.replica_pools.push(pool);
+        }
+
+        Ok(wrapper)
+    }
+
+    /// Get the most recent slow queries (newest first) with their query text,
+    /// parameter count and duration in milliseconds
+    pub fn slow_queries(&self, n: usize) -> PyResult<Vec<HashMap<String, String>>> {
+        let slow_queries = self.slow_queries.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })?;
+
+        Ok(slow_queries
+            .iter()
+            .rev()
+            .take(n)
+            .map(|entry| {
+                let mut map = HashMap::new();
+                map.insert("query".to_string(), entry.query.clone());
+                map.insert("param_count".to_string(), entry.param_count.to_string());
+                map.insert("duration_ms".to_string(), format!("{:.3}", entry.duration_ms));
+                map
+            })
+            .collect())
+    }
+
+    /// Insert a memory into the database, returning its rowid.
+    ///
+    /// With write batching enabled the row is added to the open batch and the rowid is
+    /// returned straight away, but the row is only durable and visible to other queries
+    /// once the batch commits.
+    ///
+    /// `tag` (e.g. a session id) is stored in an indexed column for exact-match lookups
+    /// with `get_by_tag`; it isn't part of the full-text index. Rows inserted without one
+    /// have no tag.
+    #[pyo3(signature = (task_description, metadata, datetime, score, tag=None))]
+    pub fn insert_memory(
+        &self,
+        task_description: &str,
+        metadata: &str,
+        datetime: &str,
+        score: f64,
+        tag: Option<&str>,
+    ) -> PyResult<i64> {
+        if let Some(batch) = self.write_batch.as_ref() {
+            let mut batch = batch.lock().map_err(|e| {
                 PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Failed to get connection: {}",
+                    "Failed to acquire write batch lock: {}",
                     e
                 ))
             })?;
+            let batch = &mut *batch;
 
-            // Main table for long-term memories
-            conn.execute(
-                "CREATE TABLE IF NOT EXISTS long_term_memories (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    task_description TEXT,
-                    metadata TEXT,
-                    datetime TEXT,
-                    score REAL
-                )",
-                [],
+            if let (Some(started), Some(max_age)) = (batch.started, batch.max_age) {
+                if started.elapsed() >= max_age {
+                    batch.commit().map_err(|e| {
+                        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                            "Failed to commit batched writes: {}",
+                            e
+                        ))
+                    })?;
+                }
+            }
+
+            if batch.started.is_none() {
+                batch.conn.execute_batch("BEGIN IMMEDIATE").map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                        "Failed to start transaction: {}",
+                        e
+                    ))
+                })?;
+                batch.started = Some(std::time::Instant::now());
+            }
+
+            self.arm_query_timeout(&batch.conn)?;
+            batch.conn.execute(
+                "INSERT INTO long_term_memories (task_description, metadata, datetime, score, tag) VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![task_description, metadata, datetime, score, tag],
             ).map_err(|e| {
                 PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Failed to create table: {}",
+                    "Failed to insert memory: {}",
                     e
                 ))
             })?;
+            let rowid = batch.conn.last_insert_rowid();
+            batch.pending += 1;
+
+            if batch.pending >= batch.max_rows {
+                batch.commit().map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                        "Failed to commit batched writes: {}",
+                        e
+                    ))
+                })?;
+            }
+            return Ok(rowid);
+        }
 
-            // FTS5 virtual table for full-text search
-            conn.execute(
-                "CREATE VIRTUAL TABLE IF NOT EXISTS long_term_memories_fts USING fts5(
-                    task_description,
-                    metadata,
-                    content='long_term_memories',
-                    content_rowid='id'
-                )",
-                [],
+        let pool = self.connection_pool.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire pool lock: {}",
+                e
+            ))
+        })?;
+
+        let conn = pool.get().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to get connection: {}",
+                e
+            ))
+        })?;
+        self.arm_query_timeout(&conn)?;
+
+        conn.execute(
+            "INSERT INTO long_term_memories (task_description, metadata, datetime, score, tag) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![task_description, metadata, datetime, score, tag],
+        ).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to insert memory: {}",
+                e
+            ))
+        })?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Commit any batched inserts now, returning how many rows were committed (always 0
+    /// when write batching is off)
+    pub fn flush_writes(&self) -> PyResult<usize> {
+        self.flush_pending()
+    }
+
+    /// Insert several memories in one transaction, then rank the new rows against a query.
+    ///
+    /// Returns `(rowid, bm25_rank)` for every inserted row in insertion order. Rows that
+    /// don't match the query are kept in the output with a rank of `inf` (BM25 ranks are
+    /// negative, lower is better, so unmatched rows sort last).
+    pub fn insert_and_rank(&self, rows: Vec<(String, String, String, f64)>, query: &str) -> PyResult<Vec<(i64, f64)>> {
+        self.flush_pending()?;
+
+        let pool = self.connection_pool.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire pool lock: {}",
+                e
+            ))
+        })?;
+
+        let mut conn = pool.get().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to get connection: {}",
+                e
+            ))
+        })?;
+        self.arm_query_timeout(&conn)?;
+
+        let tx = conn.transaction().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to start transaction: {}",
+                e
+            ))
+        })?;
+
+        let mut rowids = Vec::with_capacity(rows.len());
+        for (task_description, metadata, datetime, score) in &rows {
+            tx.execute(
+                "INSERT INTO long_term_memories (task_description, metadata, datetime, score) VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![task_description, metadata, datetime, score],
             ).map_err(|e| {
                 PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Failed to create FTS5 table: {}",
+                    "Failed to insert memory: {}",
+                    e
+                ))
+            })?;
+            rowids.push(tx.last_insert_rowid());
+        }
+
+        tx.commit().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to commit transaction: {}",
+                e
+            ))
+        })?;
+
+        let (first_id, last_id) = match (rowids.first(), rowids.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return Ok(Vec::new()),
+        };
+
+        // Rows inserted in one transaction hold contiguous rowids
+        let mut stmt = conn.prepare(
+            "SELECT rowid, bm25(long_term_memories_fts)
+             FROM long_term_memories_fts
+             WHERE long_term_memories_fts MATCH ?1 AND rowid BETWEEN ?2 AND ?3"
+        ).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to prepare query: {}",
+                e
+            ))
+        })?;
+
+        let ranks = stmt.query_map(rusqlite::params![query, first_id, last_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?))
+        }).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to execute query: {}",
+                e
+            ))
+        })?;
+
+        let mut rank_by_id = HashMap::new();
+        for rank in ranks {
+            let (rowid, bm25) = rank.map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to read row: {}",
+                    e
+                ))
+            })?;
+            rank_by_id.insert(rowid, bm25);
+        }
+
+        Ok(rowids
+            .into_iter()
+            .map(|rowid| (rowid, *rank_by_id.get(&rowid).unwrap_or(&f64::INFINITY)))
+            .collect())
+    }
+
+    /// Materialize each memory's BM25 rank for a standing query into a column.
+    ///
+    /// Within one transaction, matching rows get their rank written to `rank_column` and
+    /// all other rows have it cleared to NULL. `rank_column` must be in the allowlist
+    /// (currently only `materialized_rank`). Returns the number of matching rows updated.
+    pub fn materialize_ranks(&self, query: &str, rank_column: &str) -> PyResult<usize> {
+        self.flush_pending()?;
+
+        if !MATERIALIZABLE_RANK_COLUMNS.contains(&rank_column) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid rank column '{}': expected one of {:?}",
+                rank_column, MATERIALIZABLE_RANK_COLUMNS
+            )));
+        }
+
+        let pool = self.connection_pool.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire pool lock: {}",
+                e
+            ))
+        })?;
+
+        let mut conn = pool.get().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to get connection: {}",
+                e
+            ))
+        })?;
+        self.arm_query_timeout(&conn)?;
+
+        let tx = conn.transaction().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to start transaction: {}",
+                e
+            ))
+        })?;
+
+        let ranks: Vec<(i64, f64)> = {
+            let mut stmt = tx.prepare(
+                "SELECT rowid, bm25(long_term_memories_fts)
+                 FROM long_term_memories_fts
+                 WHERE long_term_memories_fts MATCH ?1"
+            ).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to prepare query: {}",
+                    e
+                ))
+            })?;
+
+            let rows = stmt.query_map([query], |row| Ok((row.get(0)?, row.get(1)?))).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to execute query: {}",
                     e
                 ))
             })?;
 
-            // Create triggers to keep FTS5 in sync
-            conn.execute_batch(
-                "CREATE TRIGGER IF NOT EXISTS long_term_memories_ai AFTER INSERT ON long_term_memories BEGIN
-                    INSERT INTO long_term_memories_fts(rowid, task_description, metadata)
-                    VALUES (new.id, new.task_description, new.metadata);
-                END;
-                CREATE TRIGGER IF NOT EXISTS long_term_memories_ad AFTER DELETE ON long_term_memories BEGIN
-                    INSERT INTO long_term_memories_fts(long_term_memories_fts, rowid, task_description, metadata)
-                    VALUES('delete', old.id, old.task_description, old.metadata);
-                END;
-                CREATE TRIGGER IF NOT EXISTS long_term_memories_au AFTER UPDATE ON long_term_memories BEGIN
-                    INSERT INTO long_term_memories_fts(long_term_memories_fts, rowid, task_description, metadata)
-                    VALUES('delete', old.id, old.task_description, old.metadata);
-                    INSERT INTO long_term_memories_fts(rowid, task_description, metadata)
-                    VALUES (new.id, new.task_description, new.metadata);
-                END;"
-            ).map_err(|e| {
-                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Failed to create FTS5 triggers: {}",
-                    e
-                ))
-            })?;
+            rows.collect::<rusqlite::Result<Vec<_>>>().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to read row: {}",
+                    e
+                ))
+            })?
+        };
+
+        // rank_column is validated against the allowlist above
+        tx.execute(&format!("UPDATE long_term_memories SET {} = NULL", rank_column), []).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to clear ranks: {}",
+                e
+            ))
+        })?;
+
+        {
+            let mut stmt = tx
+                .prepare(&format!("UPDATE long_term_memories SET {} = ?1 WHERE id = ?2", rank_column))
+                .map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                        "Failed to prepare query: {}",
+                        e
+                    ))
+                })?;
+
+            for (rowid, rank) in &ranks {
+                stmt.execute(rusqlite::params![rank, rowid]).map_err(|e| {
+                    PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                        "Failed to write rank: {}",
+                        e
+                    ))
+                })?;
+            }
+        }
+
+        tx.commit().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to commit transaction: {}",
+                e
+            ))
+        })?;
+
+        Ok(ranks.len())
+    }
+
+    /// Full-text search using FTS5 - returns memories matching the query.
+    ///
+    /// `ranker` picks the ranking: `"bm25"` (default) calls `bm25()` with unweighted
+    /// columns, while `"rank"` orders by the FTS5 `rank` column, which evaluates whatever
+    /// ranking function is configured on the table (set with
+    /// `INSERT INTO long_term_memories_fts(long_term_memories_fts, rank) VALUES('rank', ...)`).
+    /// Until one is configured `rank` is plain `bm25()`, so both give the same order. Either
+    /// way lower values rank first and are returned under `"rank"`; ties are broken by id.
+    #[pyo3(signature = (query, limit, ranker="bm25"))]
+    pub fn search_memories(&self, query: &str, limit: usize, ranker: &str) -> PyResult<Vec<HashMap<String, String>>> {
+        let ranker = FtsRanker::parse(ranker)?;
+        let conn = self.read_connection()?;
+
+        let start_time = std::time::Instant::now();
+
+        // Use FTS5 MATCH for full-text search with the chosen ranking; equal ranks are
+        // broken by id so result order is reproducible across runs
+        let mut stmt = conn.prepare(&format!(
+            "SELECT m.id, m.task_description, m.metadata, m.datetime, m.score,
+                    {} as rank
+             FROM long_term_memories m
+             JOIN long_term_memories_fts fts ON m.id = fts.rowid
+             WHERE long_term_memories_fts MATCH ?1
+             ORDER BY rank, m.id
+             LIMIT ?2",
+            ranker.sql()
+        )).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to prepare query: {}",
+                e
+            ))
+        })?;
+
+        let rows = stmt.query_map(rusqlite::params![query, limit as i64], |row| {
+            let mut map = HashMap::new();
+            map.insert("id".to_string(), row.get::<_, i64>(0)?.to_string());
+            map.insert("task_description".to_string(), row.get::<_, String>(1)?);
+            map.insert("metadata".to_string(), row.get::<_, String>(2)?);
+            map.insert("datetime".to_string(), row.get::<_, String>(3)?);
+            map.insert("score".to_string(), row.get::<_, f64>(4)?.to_string());
+            map.insert("rank".to_string(), row.get::<_, f64>(5)?.to_string());
+            Ok(map)
+        }).map_err(|e| Self::query_error("Failed to execute query", e))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row.map_err(|e| Self::query_error("Failed to read row", e))?);
+        }
+
+        self.record_query_time(&format!("search_memories MATCH {}", query), 2, start_time.elapsed());
+
+        Ok(results)
+    }
+
+    /// Get up to `limit` memories whose `tag` (set by `insert_memory`) equals `tag` exactly,
+    /// oldest first. This complements `search_memories`: an indexed filter with no text
+    /// matching or scoring, for lookups like "everything from session X". Rows carry the
+    /// same fields as `search_memories` results plus `tag`, without `rank`.
+    pub fn get_by_tag(&self, tag: &str, limit: usize) -> PyResult<Vec<HashMap<String, String>>> {
+        let conn = self.read_connection()?;
+
+        let start_time = std::time::Instant::now();
+
+        let mut stmt = conn.prepare(
+            "SELECT id, task_description, metadata, datetime, score, tag
+             FROM long_term_memories
+             WHERE tag = ?1
+             ORDER BY id
+             LIMIT ?2"
+        ).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to prepare query: {}",
+                e
+            ))
+        })?;
+
+        let rows = stmt.query_map(rusqlite::params![tag, limit as i64], |row| {
+            let mut map = HashMap::new();
+            map.insert("id".to_string(), row.get::<_, i64>(0)?.to_string());
+            map.insert("task_description".to_string(), row.get::<_, String>(1)?);
+            map.insert("metadata".to_string(), row.get::<_, String>(2)?);
+            map.insert("datetime".to_string(), row.get::<_, String>(3)?);
+            map.insert("score".to_string(), row.get::<_, f64>(4)?.to_string());
+            map.insert("tag".to_string(), row.get::<_, String>(5)?);
+            Ok(map)
+        }).map_err(|e| Self::query_error("Failed to execute query", e))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row.map_err(|e| Self::query_error("Failed to read row", e))?);
+        }
+
+        self.record_query_time("get_by_tag", 2, start_time.elapsed());
+
+        Ok(results)
+    }
+
+    /// Retrieve up to `candidates` memories with FTS5/BM25, then rerank them against the
+    /// query and return the best `limit`.
+    ///
+    /// `rerank_metric` chooses how candidates are rescored from their term frequencies
+    /// (tokenized like RustMemoryStorage): `"cosine"` (default), `"jaccard"` or
+    /// `"overlap"` (count of distinct query terms present). The cosine is over plain term
+    /// frequencies, not TF-IDF: a handful of candidates gives no useful IDF weights, and
+    /// BM25 has already weighted rare terms during retrieval. Each result carries its score
+    /// under `"rerank_score"`; equal scores keep their BM25 order.
+    #[pyo3(signature = (query, candidates, limit, rerank_metric="cosine"))]
+    pub fn search_and_rerank(
+        &self,
+        query: &str,
+        candidates: usize,
+        limit: usize,
+        rerank_metric: &str,
+    ) -> PyResult<Vec<HashMap<String, String>>> {
+        let metric = SimilarityMetric::parse(rerank_metric)?;
+        let tokenizer = TokenizerConfig::default();
+        let query_frequencies = tokenizer.compute_word_frequencies(query);
+
+        let mut reranked: Vec<(f64, HashMap<String, String>)> = self
+            .search_memories(query, candidates, "bm25")?
+            .into_iter()
+            .map(|row| {
+                let description = row.get("task_description").map(String::as_str).unwrap_or("");
+                let score = metric.score(&query_frequencies, &tokenizer.compute_word_frequencies(description));
+                (score, row)
+            })
+            .collect();
+
+        // Stable sort, so ties keep the BM25 order from retrieval
+        reranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        Ok(reranked
+            .into_iter()
+            .take(limit)
+            .map(|(score, mut row)| {
+                row.insert("rerank_score".to_string(), score.to_string());
+                row
+            })
+            .collect())
+    }
+
+    /// Find memories where `term_a` and `term_b` occur within `distance` tokens of each
+    /// other, ranked by BM25. Both terms are quoted, so they can't inject FTS syntax.
+    pub fn search_near(
+        &self,
+        term_a: &str,
+        term_b: &str,
+        distance: usize,
+        limit: usize,
+    ) -> PyResult<Vec<HashMap<String, String>>> {
+        if term_a.trim().is_empty() || term_b.trim().is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "search_near terms must not be empty",
+            ));
         }
 
-        Ok(RustSQLiteWrapper {
-            connection_pool: Arc::new(Mutex::new(pool)),
-        })
+        let expression = format!(
+            "NEAR({} {}, {})",
+            Self::fts_quote(term_a),
+            Self::fts_quote(term_b),
+            distance
+        );
+        self.search_memories(&expression, limit, "bm25")
     }
 
-    /// Insert a memory into the database
-    pub fn insert_memory(&self, task_description: &str, metadata: &str, datetime: &str, score: f64) -> PyResult<i64> {
+    /// Time a query over `iterations` runs and return `min_ms`, `max_ms`, `mean_ms` and
+    /// `median_ms` (milliseconds, including stepping through every result row).
+    ///
+    /// One extra warm-up run happens first and is not measured, and the statement is
+    /// prepared once via the statement cache, so the figures reflect steady-state cost.
+    /// The query really executes on every iteration, so only benchmark read-only queries.
+    pub fn benchmark_query(&self, query: &str, params: Bound<'_, PyDict>, iterations: usize) -> PyResult<HashMap<String, f64>> {
+        if iterations == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "iterations must be at least 1",
+            ));
+        }
+
         let pool = self.connection_pool.lock().map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
                 "Failed to acquire pool lock: {}",
@@ -991,21 +6775,78 @@ impl RustSQLiteWrapper {
             ))
         })?;
 
-        conn.execute(
-            "INSERT INTO long_term_memories (task_description, metadata, datetime, score) VALUES (?1, ?2, ?3, ?4)",
-            rusqlite::params![task_description, metadata, datetime, score],
-        ).map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to insert memory: {}",
-                e
-            ))
-        })?;
+        // Convert PyDict to named parameters
+        let mut param_values: Vec<(String, String)> = Vec::new();
+        for (key, value) in params.iter() {
+            let key_str: String = key.extract()?;
+            let value_str: String = value.extract()?;
+            param_values.push((key_str, value_str));
+        }
+        let params_slice: Vec<(&str, &dyn rusqlite::ToSql)> = param_values
+            .iter()
+            .map(|(k, v)| (k.as_str(), v as &dyn rusqlite::ToSql))
+            .collect();
 
-        Ok(conn.last_insert_rowid())
+        let mut timings_ms = Vec::with_capacity(iterations);
+        for run in 0..=iterations {
+            self.arm_query_timeout(&conn)?;
+            let start_time = std::time::Instant::now();
+
+            let mut stmt = conn.prepare_cached(query).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to prepare query: {}",
+                    e
+                ))
+            })?;
+            let mut rows = stmt
+                .query(params_slice.as_slice())
+                .map_err(|e| Self::query_error("Failed to execute query", e))?;
+            while rows
+                .next()
+                .map_err(|e| Self::query_error("Failed to read row", e))?
+                .is_some()
+            {}
+
+            // The first run only warms the statement cache and page cache
+            if run > 0 {
+                timings_ms.push(start_time.elapsed().as_secs_f64() * 1000.0);
+            }
+        }
+
+        timings_ms.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let mid = timings_ms.len() / 2;
+        let median = if timings_ms.len() % 2 == 0 {
+            (timings_ms[mid - 1] + timings_ms[mid]) / 2.0
+        } else {
+            timings_ms[mid]
+        };
+
+        let mut result = HashMap::new();
+        result.insert("min_ms".to_string(), timings_ms[0]);
+        result.insert("max_ms".to_string(), timings_ms[timings_ms.len() - 1]);
+        result.insert("mean_ms".to_string(), timings_ms.iter().sum::<f64>() / timings_ms.len() as f64);
+        result.insert("median_ms".to_string(), median);
+        Ok(result)
     }
 
-    /// Full-text search using FTS5 - returns memories matching the query
-    pub fn search_memories(&self, query: &str, limit: usize) -> PyResult<Vec<HashMap<String, String>>> {
+    /// Count memories per `"day"` (labels like `2024-01-31`) or `"hour"` (labels like
+    /// `2024-01-31T09:00`), returned as (label, count) pairs in chronological order.
+    ///
+    /// The `datetime` column is expected to hold ISO-8601 text such as
+    /// `2024-01-31T09:15:00`, `2024-01-31 09:15:00.123` or `2024-01-31T09:15:00+02:00`
+    /// (offsets are converted to UTC). Rows whose datetime SQLite cannot parse are skipped.
+    pub fn creation_histogram(&self, bucket: &str) -> PyResult<Vec<(String, i64)>> {
+        let format = match bucket {
+            "day" => "%Y-%m-%d",
+            "hour" => "%Y-%m-%dT%H:00",
+            other => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Invalid bucket '{}': expected 'day' or 'hour'",
+                    other
+                )))
+            }
+        };
+
         let pool = self.connection_pool.lock().map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
                 "Failed to acquire pool lock: {}",
@@ -1019,16 +6860,14 @@ impl RustSQLiteWrapper {
                 e
             ))
         })?;
+        self.arm_query_timeout(&conn)?;
 
-        // Use FTS5 MATCH for full-text search with BM25 ranking
         let mut stmt = conn.prepare(
-            "SELECT m.id, m.task_description, m.metadata, m.datetime, m.score,
-                    bm25(long_term_memories_fts) as rank
-             FROM long_term_memories m
-             JOIN long_term_memories_fts fts ON m.id = fts.rowid
-             WHERE long_term_memories_fts MATCH ?1
-             ORDER BY rank
-             LIMIT ?2"
+            "SELECT strftime(?1, datetime) AS label, COUNT(*)
+             FROM long_term_memories
+             WHERE label IS NOT NULL
+             GROUP BY label
+             ORDER BY label"
         ).map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
                 "Failed to prepare query: {}",
@@ -1036,50 +6875,20 @@ impl RustSQLiteWrapper {
             ))
         })?;
 
-        let rows = stmt.query_map(rusqlite::params![query, limit as i64], |row| {
-            let mut map = HashMap::new();
-            map.insert("id".to_string(), row.get::<_, i64>(0)?.to_string());
-            map.insert("task_description".to_string(), row.get::<_, String>(1)?);
-            map.insert("metadata".to_string(), row.get::<_, String>(2)?);
-            map.insert("datetime".to_string(), row.get::<_, String>(3)?);
-            map.insert("score".to_string(), row.get::<_, f64>(4)?.to_string());
-            map.insert("rank".to_string(), row.get::<_, f64>(5)?.to_string());
-            Ok(map)
-        }).map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to execute query: {}",
-                e
-            ))
-        })?;
+        let rows = stmt
+            .query_map([format], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))
+            .map_err(|e| Self::query_error("Failed to execute query", e))?;
 
-        let mut results = Vec::new();
+        let mut histogram = Vec::new();
         for row in rows {
-            results.push(row.map_err(|e| {
-                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Failed to read row: {}",
-                    e
-                ))
-            })?);
+            histogram.push(row.map_err(|e| Self::query_error("Failed to read row", e))?);
         }
-
-        Ok(results)
+        Ok(histogram)
     }
 
     /// Execute a raw SELECT query and return results
     pub fn execute_query(&self, query: &str, params: Bound<'_, PyDict>) -> PyResult<Vec<HashMap<String, String>>> {
-        let pool = self.connection_pool.lock().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to acquire pool lock: {}",
-                e
-            ))
-        })?;
-
-        let conn = pool.get().map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to get connection: {}",
-                e
-            ))
-        })?;
+        let conn = self.read_connection()?;
 
         // Convert PyDict to named parameters
         let mut param_values: Vec<(String, String)> = Vec::new();
@@ -1089,6 +6898,8 @@ impl RustSQLiteWrapper {
             param_values.push((key_str, value_str));
         }
 
+        let start_time = std::time::Instant::now();
+
         let mut stmt = conn.prepare(query).map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
                 "Failed to prepare query: {}",
@@ -1120,28 +6931,22 @@ impl RustSQLiteWrapper {
                 map.insert(col_name.clone(), value);
             }
             Ok(map)
-        }).map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to execute query: {}",
-                e
-            ))
-        })?;
+        }).map_err(|e| Self::query_error("Failed to execute query", e))?;
 
         let mut results = Vec::new();
         for row in rows {
-            results.push(row.map_err(|e| {
-                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Failed to read row: {}",
-                    e
-                ))
-            })?);
+            results.push(row.map_err(|e| Self::query_error("Failed to read row", e))?);
         }
 
+        self.record_query_time(query, param_values.len(), start_time.elapsed());
+
         Ok(results)
     }
 
     /// Execute an INSERT/UPDATE/DELETE query
     pub fn execute_update(&self, query: &str, params: Bound<'_, PyDict>) -> PyResult<usize> {
+        self.flush_pending()?;
+
         let pool = self.connection_pool.lock().map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
                 "Failed to acquire pool lock: {}",
@@ -1155,6 +6960,7 @@ impl RustSQLiteWrapper {
                 e
             ))
         })?;
+        self.arm_query_timeout(&conn)?;
 
         // Convert PyDict to named parameters
         let mut param_values: Vec<(String, String)> = Vec::new();
@@ -1169,18 +6975,27 @@ impl RustSQLiteWrapper {
             .map(|(k, v)| (k.as_str(), v as &dyn rusqlite::ToSql))
             .collect();
 
-        let affected = conn.execute(query, params_slice.as_slice()).map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to execute update: {}",
-                e
-            ))
-        })?;
+        let start_time = std::time::Instant::now();
+        let affected = conn
+            .execute(query, params_slice.as_slice())
+            .map_err(|e| Self::query_error("Failed to execute update", e))?;
+
+        self.record_query_time(query, param_values.len(), start_time.elapsed());
 
         Ok(affected)
     }
 
-    /// Execute multiple queries in a batch within a transaction
-    pub fn execute_batch(&self, queries: Bound<'_, PyList>) -> PyResult<Vec<usize>> {
+    /// Execute multiple queries in a batch within a transaction.
+    ///
+    /// With `dry_run=True` every statement still runs inside the transaction, which is
+    /// then always rolled back, so the returned counts are what *would* have been
+    /// affected. Rollback undoes table changes only; side effects of SQLite functions
+    /// called by the statements are not undone. Errors name the failing statement index;
+    /// an item that isn't a `(query, params)` tuple of strings raises `TypeError`.
+    #[pyo3(signature = (queries, dry_run=false))]
+    pub fn execute_batch(&self, queries: Bound<'_, PyList>, dry_run: bool) -> PyResult<Vec<usize>> {
+        self.flush_pending()?;
+
         let pool = self.connection_pool.lock().map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
                 "Failed to acquire pool lock: {}",
@@ -1194,6 +7009,7 @@ impl RustSQLiteWrapper {
                 e
             ))
         })?;
+        self.arm_query_timeout(&conn)?;
 
         // Use a transaction for batch operations
         let tx = conn.transaction().map_err(|e| {
@@ -1205,36 +7021,35 @@ impl RustSQLiteWrapper {
 
         let mut results = Vec::new();
 
-        for item in queries.iter() {
-            // Each item should be a tuple of (query, params_dict)
-            if let Ok(tuple) = item.downcast::<PyTuple>() {
-                if tuple.len() == 2 {
-                    let query: String = tuple.get_item(0)?.extract()?;
-                    let params: Bound<'_, PyDict> = tuple.get_item(1)?.downcast()?.clone();
-
-                    // Convert params
-                    let mut param_values: Vec<(String, String)> = Vec::new();
-                    for (key, value) in params.iter() {
-                        let key_str: String = key.extract()?;
-                        let value_str: String = value.extract()?;
-                        param_values.push((key_str, value_str));
-                    }
+        for (index, item) in queries.iter().enumerate() {
+            // Each item must be a tuple of (query, params_dict) with string values
+            let (query, param_values) = Self::batch_item(&item).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
+                    "Batch query {} must be a (str, dict[str, str]) tuple: {}",
+                    index, e
+                ))
+            })?;
 
-                    let params_slice: Vec<(&str, &dyn rusqlite::ToSql)> = param_values
-                        .iter()
-                        .map(|(k, v)| (k.as_str(), v as &dyn rusqlite::ToSql))
-                        .collect();
+            let params_slice: Vec<(&str, &dyn rusqlite::ToSql)> = param_values
+                .iter()
+                .map(|(k, v)| (k.as_str(), v as &dyn rusqlite::ToSql))
+                .collect();
 
-                    let affected = tx.execute(&query, params_slice.as_slice()).map_err(|e| {
-                        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                            "Failed to execute batch query: {}",
-                            e
-                        ))
-                    })?;
+            let affected = tx
+                .execute(&query, params_slice.as_slice())
+                .map_err(|e| Self::query_error(&format!("Failed to execute batch query {}", index), e))?;
 
-                    results.push(affected);
-                }
-            }
+            results.push(affected);
+        }
+
+        if dry_run {
+            tx.rollback().map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to roll back transaction: {}",
+                    e
+                ))
+            })?;
+            return Ok(results);
         }
 
         tx.commit().map_err(|e| {
@@ -1247,6 +7062,163 @@ impl RustSQLiteWrapper {
         Ok(results)
     }
 
+    /// Compute a stable checksum over the logical content of `long_term_memories`.
+    ///
+    /// Rows are hashed in id order (id, task_description, metadata, datetime, score), so
+    /// two databases with identical rows produce the same hex digest regardless of page
+    /// layout, vacuum state or FTS index contents.
+    pub fn content_checksum(&self) -> PyResult<String> {
+        use std::hash::Hasher;
+
+        let pool = self.connection_pool.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire pool lock: {}",
+                e
+            ))
+        })?;
+
+        let conn = pool.get().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to get connection: {}",
+                e
+            ))
+        })?;
+        self.arm_query_timeout(&conn)?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, task_description, metadata, datetime, score
+             FROM long_term_memories
+             ORDER BY id"
+        ).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to prepare query: {}",
+                e
+            ))
+        })?;
+
+        let mut rows = stmt.query([]).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to execute query: {}",
+                e
+            ))
+        })?;
+
+        let mut hasher = Fnv1aHasher::default();
+        let read_error = |e: rusqlite::Error| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to read row: {}", e))
+        };
+        while let Some(row) = rows.next().map_err(read_error)? {
+            hasher.write(&row.get::<_, i64>(0).map_err(read_error)?.to_le_bytes());
+            // Length-prefix text fields so adjacent values can't run together
+            for column in 1..4 {
+                match row.get::<_, Option<String>>(column).map_err(read_error)? {
+                    Some(text) => {
+                        hasher.write_u8(1);
+                        hasher.write(&(text.len() as u64).to_le_bytes());
+                        hasher.write(text.as_bytes());
+                    }
+                    None => hasher.write_u8(0),
+                }
+            }
+            match row.get::<_, Option<f64>>(4).map_err(read_error)? {
+                Some(score) => {
+                    hasher.write_u8(1);
+                    hasher.write(&score.to_bits().to_le_bytes());
+                }
+                None => hasher.write_u8(0),
+            }
+        }
+
+        Ok(format!("{:016x}", hasher.finish()))
+    }
+
+    /// Check that the FTS index covers exactly the rows of `long_term_memories`.
+    ///
+    /// `long_term_memories_fts` is an external-content table, so reading it returns the
+    /// base table's rows whatever the index holds, and `PRAGMA integrity_check` doesn't
+    /// compare the two. This compares base-table ids against the ids in the FTS index's
+    /// `_docsize` shadow table instead and returns:
+    ///
+    /// - `base_rows`: rows in `long_term_memories`
+    /// - `fts_rows`: rows indexed in `long_term_memories_fts`
+    /// - `missing_from_fts`: base rows with no index entry (not searchable)
+    /// - `orphaned_fts`: index entries whose base row no longer exists
+    /// - `mismatches`: `missing_from_fts + orphaned_fts`
+    ///
+    /// A nonzero `mismatches` means the sync triggers were bypassed or dropped; call
+    /// `rebuild_fts` to re-index. Rows whose indexed text is stale but whose id is still
+    /// present are not detected.
+    pub fn verify_fts_consistency(&self) -> PyResult<HashMap<String, i64>> {
+        let pool = self.connection_pool.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire pool lock: {}",
+                e
+            ))
+        })?;
+
+        let conn = pool.get().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to get connection: {}",
+                e
+            ))
+        })?;
+        self.arm_query_timeout(&conn)?;
+
+        let checks = [
+            ("base_rows", "SELECT COUNT(*) FROM long_term_memories"),
+            ("fts_rows", "SELECT COUNT(*) FROM long_term_memories_fts_docsize"),
+            (
+                "missing_from_fts",
+                "SELECT COUNT(*) FROM long_term_memories m
+                 WHERE NOT EXISTS (SELECT 1 FROM long_term_memories_fts_docsize d WHERE d.id = m.id)",
+            ),
+            (
+                "orphaned_fts",
+                "SELECT COUNT(*) FROM long_term_memories_fts_docsize d
+                 WHERE NOT EXISTS (SELECT 1 FROM long_term_memories m WHERE m.id = d.id)",
+            ),
+        ];
+
+        let mut counts = HashMap::new();
+        for (name, query) in checks {
+            let count: i64 = conn
+                .query_row(query, [], |row| row.get(0))
+                .map_err(|e| Self::query_error("Failed to count FTS rows", e))?;
+            counts.insert(name.to_string(), count);
+        }
+        let mismatches = counts["missing_from_fts"] + counts["orphaned_fts"];
+        counts.insert("mismatches".to_string(), mismatches);
+
+        Ok(counts)
+    }
+
+    /// Rebuild the FTS index from `long_term_memories`, discarding whatever it held
+    pub fn rebuild_fts(&self) -> PyResult<()> {
+        self.flush_pending()?;
+
+        let pool = self.connection_pool.lock().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to acquire pool lock: {}",
+                e
+            ))
+        })?;
+
+        let conn = pool.get().map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to get connection: {}",
+                e
+            ))
+        })?;
+        self.arm_query_timeout(&conn)?;
+
+        conn.execute(
+            "INSERT INTO long_term_memories_fts(long_term_memories_fts) VALUES('rebuild')",
+            [],
+        )
+        .map_err(|e| Self::query_error("Failed to rebuild FTS index", e))?;
+        Ok(())
+    }
+
     /// Get all memories ordered by datetime (most recent first)
     pub fn get_all_memories(&self, limit: usize) -> PyResult<Vec<HashMap<String, String>>> {
         let pool = self.connection_pool.lock().map_err(|e| {
@@ -1262,6 +7234,7 @@ impl RustSQLiteWrapper {
                 e
             ))
         })?;
+        self.arm_query_timeout(&conn)?;
 
         let mut stmt = conn.prepare(
             "SELECT id, task_description, metadata, datetime, score
@@ -1304,13 +7277,153 @@ impl RustSQLiteWrapper {
     }
 }
 
+/// Recall from both memory tiers at once: the in-memory store (recent context) and the
+/// SQLite long-term memories, merged into one ranking of up to `limit` results.
+///
+/// The store is searched like `RustMemoryStorage.search_full` (counting as an access of
+/// its returned items), leaving out items that share no query term, and the database like
+/// `search_memories` with BM25, matching any of the query's words so natural-language
+/// queries need no FTS syntax. Cosine scores
+/// (higher is better, at most 1) and BM25 ranks (lower is better, unbounded) aren't
+/// comparable, so each tier is normalized on its own: its relevances (cosine score, or
+/// negated BM25 rank) are divided by its best one, giving each tier's top hit a
+/// `relevance` of 1. Results are then merged by relevance, in-memory results first on
+/// ties. Content that differs only in case and whitespace counts as a duplicate and
+/// only its best-ranked copy is kept; each tier is over-fetched so that dropping
+/// duplicates doesn't leave the results short.
+///
+/// Every result has `content`, `relevance` and `source` (`"memory"` or `"sqlite"`). In-memory
+/// results add `id` and `similarity` (the raw cosine score); database results keep every
+/// `search_memories` field, including `rank`.
+#[pyfunction]
+fn hybrid_recall(
+    py: Python<'_>,
+    mem: PyRef<'_, RustMemoryStorage>,
+    db: PyRef<'_, RustSQLiteWrapper>,
+    query: &str,
+    limit: usize,
+) -> PyResult<Vec<HashMap<String, String>>> {
+    let mem = &*mem;
+    let db = &*db;
+    if limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    let fts_query = query
+        .split_whitespace()
+        .map(RustSQLiteWrapper::fts_quote)
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    py.allow_threads(|| {
+        // Duplicates only show up once the tiers are merged, so fetch more than `limit`
+        // from each and double that until the merged top `limit` can't change
+        let mut fetch = limit.saturating_mul(2);
+        loop {
+            let mut merged: Vec<(f64, HashMap<String, String>)> = Vec::new();
+            // Lowest relevance fetched from each tier that may still hold more candidates
+            let mut cutoffs = Vec::new();
+
+            let mem_hits: Vec<_> = mem
+                .ranked_search(query, fetch, false, 0.0, 0, 0.0, false)?
+                .into_iter()
+                .filter(|(_, _, score)| *score > 0.0)
+                .collect();
+            let best = mem_hits.iter().map(|(_, _, score)| *score).fold(0.0, f64::max);
+            let mem_open = mem_hits.len() == fetch;
+            for (id, content, score) in mem_hits {
+                let mut result = HashMap::new();
+                result.insert("id".to_string(), id.to_string());
+                result.insert("content".to_string(), content);
+                result.insert("similarity".to_string(), score.to_string());
+                result.insert("source".to_string(), "memory".to_string());
+                merged.push((score / best, result));
+            }
+            if mem_open {
+                cutoffs.extend(merged.last().map(|(relevance, _)| *relevance));
+            }
+
+            if !fts_query.is_empty() {
+                let rows = db.search_memories(&fts_query, fetch, "bm25")?;
+                let db_open = rows.len() == fetch;
+                let relevances: Vec<f64> = rows
+                    .iter()
+                    .map(|row| -row.get("rank").and_then(|rank| rank.parse::<f64>().ok()).unwrap_or(0.0))
+                    .collect();
+                let best = relevances.iter().copied().fold(0.0, f64::max);
+                for (mut row, relevance) in rows.into_iter().zip(relevances) {
+                    let content = row.get("task_description").cloned().unwrap_or_default();
+                    row.insert("content".to_string(), content);
+                    row.insert("source".to_string(), "sqlite".to_string());
+                    // A tier whose every rank is 0 has no spread to normalize: rank it all equally
+                    merged.push((if best > 0.0 { relevance / best } else { 1.0 }, row));
+                }
+                if db_open {
+                    cutoffs.extend(merged.last().map(|(relevance, _)| *relevance));
+                }
+            }
+
+            // Stable, so in-memory results stay ahead of database results with equal relevance
+            merged.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+            let mut seen = std::collections::HashSet::new();
+            let mut results: Vec<(f64, HashMap<String, String>)> = Vec::new();
+            for (relevance, result) in merged {
+                if results.len() == limit {
+                    break;
+                }
+                let normalized = result["content"].split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+                if seen.insert(normalized) {
+                    results.push((relevance, result));
+                }
+            }
+
+            // Unfetched candidates rank at or below their tier's cutoff, so they can only
+            // matter while the results are short or the last one doesn't beat every cutoff
+            let settled = match results.last() {
+                Some((last, _)) if results.len() == limit => cutoffs.iter().all(|cutoff| cutoff < last),
+                _ => cutoffs.is_empty(),
+            };
+            if !settled {
+                fetch = fetch.saturating_mul(2);
+                continue;
+            }
+
+            let recalled: std::collections::HashSet<u64> = results
+                .iter()
+                .filter(|(_, result)| result["source"] == "memory")
+                .filter_map(|(_, result)| result["id"].parse().ok())
+                .collect();
+            mem.record_accesses(&recalled)?;
+
+            return Ok(results
+                .into_iter()
+                .map(|(relevance, mut result)| {
+                    result.insert("relevance".to_string(), relevance.to_string());
+                    result
+                })
+                .collect());
+        }
+    })
+}
+
 /// Python module declaration
 #[pymodule]
 fn _core(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    // Route Rust log records to Python's logging module (ignore if already installed)
+    let _ = pyo3_log::try_init();
+
     m.add_class::<RustMemoryStorage>()?;
     m.add_class::<RustToolExecutor>()?;
     m.add_class::<AgentMessage>()?;
+    m.add_class::<Conversation>()?;
     m.add_class::<RustTaskExecutor>()?;
     m.add_class::<RustSQLiteWrapper>()?;
+    m.add_function(wrap_pyfunction!(total_memory_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(set_global_memory_cap, m)?)?;
+    m.add_function(wrap_pyfunction!(tokenize, m)?)?;
+    m.add_function(wrap_pyfunction!(federated_search, m)?)?;
+    m.add_function(wrap_pyfunction!(hybrid_recall, m)?)?;
+    m.add_function(wrap_pyfunction!(set_global_execution_limit, m)?)?;
+    m.add_function(wrap_pyfunction!(global_executions_in_flight, m)?)?;
     Ok(())
 }
@@ -0,0 +1,218 @@
+"""
+Tests for database components.
+"""
+
+import pytest
+
+
+class TestRustSQLiteWrapperCore:
+    """Test cases for the native RustSQLiteWrapper class."""
+
+    def test_query_timeout_aborts_runaway_query(self, tmp_path):
+        """Test that query_timeout_ms aborts an expensive recursive query."""
+        core = pytest.importorskip("fast_crewai._core")
+
+        db = core.RustSQLiteWrapper(str(tmp_path / "timeout.db"), 2, query_timeout_ms=50)
+        runaway = (
+            "WITH RECURSIVE counter(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM counter) "
+            "SELECT count(*) FROM counter"
+        )
+
+        with pytest.raises(RuntimeError, match="query exceeded time limit"):
+            db.execute_query(runaway, {})
+
+        # The connection stays usable for ordinary queries afterwards
+        assert db.execute_query("SELECT 1 AS one", {}) == [{"one": "1"}]
+
+    def test_search_near_matches_only_close_terms(self, tmp_path):
+        """Test that search_near only returns memories with the terms close together."""
+        core = pytest.importorskip("fast_crewai._core")
+
+        db = core.RustSQLiteWrapper(str(tmp_path / "near.db"), 2)
+        db.insert_memory("the rust compiler is fast", "{}", "2024-01-01", 1.0)
+        db.insert_memory(
+            "rust is a language and after many unrelated words the compiler appears",
+            "{}",
+            "2024-01-01",
+            1.0,
+        )
+
+        results = db.search_near("rust", "compiler", 2, 10)
+        assert [r["task_description"] for r in results] == ["the rust compiler is fast"]
+
+        # FTS operators inside a term are matched literally rather than interpreted
+        assert db.search_near('rust" OR "language', "compiler", 2, 10) == []
+
+    def test_search_memories_breaks_rank_ties_by_id(self, tmp_path):
+        """Test that equally relevant memories come back in a stable id order."""
+        core = pytest.importorskip("fast_crewai._core")
+
+        db = core.RustSQLiteWrapper(str(tmp_path / "ties.db"), 2)
+        ids = [db.insert_memory("deploy service", "{}", "2024-01-01", 1.0) for _ in range(5)]
+
+        for _ in range(3):
+            results = db.search_memories("deploy", 10)
+            assert [int(r["id"]) for r in results] == ids
+
+    def test_with_replicas_routes_reads_and_writes(self, tmp_path):
+        """Test that writes hit the primary and reads rotate across replicas."""
+        core = pytest.importorskip("fast_crewai._core")
+
+        replicas = []
+        for name in ("replica_a", "replica_b"):
+            path = str(tmp_path / f"{name}.db")
+            core.RustSQLiteWrapper(path, 1).insert_memory(name, "{}", "2024-01-01", 1.0)
+            replicas.append(path)
+
+        primary = str(tmp_path / "primary.db")
+        db = core.RustSQLiteWrapper.with_replicas(primary, replicas, 2)
+        db.insert_memory("primary row", "{}", "2024-01-01", 1.0)
+
+        query = "SELECT task_description FROM long_term_memories"
+        seen = [db.execute_query(query, {})[0]["task_description"] for _ in range(4)]
+        assert seen == ["replica_a", "replica_b", "replica_a", "replica_b"]
+
+        reader = core.RustSQLiteWrapper(primary, 1)
+        assert reader.execute_query(query, {}) == [{"task_description": "primary row"}]
+
+    def test_verify_fts_consistency_detects_unindexed_rows(self, tmp_path):
+        """Test that rows written around the FTS triggers are reported and rebuilt."""
+        core = pytest.importorskip("fast_crewai._core")
+
+        db = core.RustSQLiteWrapper(str(tmp_path / "fts.db"), 1)
+        db.insert_memory("indexed row", "{}", "2024-01-01", 1.0)
+        assert db.verify_fts_consistency()["mismatches"] == 0
+
+        db.execute_update("DROP TRIGGER long_term_memories_ai", {})
+        db.insert_memory("unindexed row", "{}", "2024-01-01", 1.0)
+
+        counts = db.verify_fts_consistency()
+        assert counts["base_rows"] == 2
+        assert counts["fts_rows"] == 1
+        assert counts["missing_from_fts"] == 1
+        assert counts["orphaned_fts"] == 0
+        assert counts["mismatches"] == 1
+
+        db.rebuild_fts()
+        assert db.verify_fts_consistency()["mismatches"] == 0
+        assert len(db.search_memories("unindexed", 5)) == 1
+
+    def test_write_batching_commits_on_threshold_and_flush(self, tmp_path):
+        """Test that batched inserts become visible once the batch commits."""
+        core = pytest.importorskip("fast_crewai._core")
+
+        path = str(tmp_path / "batched.db")
+        db = core.RustSQLiteWrapper(path, 2, write_batch_rows=3)
+        reader = core.RustSQLiteWrapper(path, 1)
+        count = "SELECT COUNT(*) AS n FROM long_term_memories"
+
+        ids = [db.insert_memory(f"row {i}", "{}", "2024-01-01", 1.0) for i in range(4)]
+        assert ids == sorted(set(ids))
+        assert reader.execute_query(count, {}) == [{"n": "3"}]
+
+        assert db.flush_writes() == 1
+        assert reader.execute_query(count, {}) == [{"n": "4"}]
+        assert db.flush_writes() == 0
+
+    def test_search_memories_rank_column_uses_configured_ranking(self, tmp_path):
+        """Test choosing between bm25() and the FTS5 rank column."""
+        core = pytest.importorskip("fast_crewai._core")
+
+        db = core.RustSQLiteWrapper(str(tmp_path / "ranker.db"), 1)
+        db.insert_memory("deploy the service", "{}", "2024-01-01", 1.0)
+        db.insert_memory("deploy deploy the service again", "{}", "2024-01-01", 1.0)
+
+        bm25 = db.search_memories("deploy", 10)
+        assert db.search_memories("deploy", 10, ranker="rank") == bm25
+
+        db.execute_update(
+            "INSERT INTO long_term_memories_fts(long_term_memories_fts, rank) VALUES('rank', 'bm25(10.0)')",
+            {},
+        )
+        weighted = db.search_memories("deploy", 10, ranker="rank")
+        assert [r["id"] for r in weighted] == [r["id"] for r in bm25]
+        assert float(weighted[0]["rank"]) != pytest.approx(float(bm25[0]["rank"]))
+        assert db.search_memories("deploy", 10) == bm25
+
+        with pytest.raises(ValueError, match="Invalid ranker"):
+            db.search_memories("deploy", 10, ranker="tfidf")
+
+    def test_get_by_tag_filters_exactly(self, tmp_path):
+        """Test exact-match retrieval by tag alongside untagged inserts."""
+        core = pytest.importorskip("fast_crewai._core")
+
+        db = core.RustSQLiteWrapper(str(tmp_path / "tags.db"), 1)
+        first = db.insert_memory("deploy the service", "{}", "2024-01-01", 1.0, tag="session-1")
+        db.insert_memory("deploy the service", "{}", "2024-01-01", 1.0, tag="session-2")
+        second = db.insert_memory("rotate keys", "{}", "2024-01-02", 0.5, tag="session-1")
+        db.insert_memory("untagged", "{}", "2024-01-03", 1.0)
+
+        results = db.get_by_tag("session-1", 10)
+        assert [int(r["id"]) for r in results] == [first, second]
+        assert results[1]["tag"] == "session-1"
+        assert len(db.get_by_tag("session-1", 1)) == 1
+        assert db.get_by_tag("session", 10) == []
+        assert len(db.search_memories("deploy", 10)) == 2
+
+    def test_search_and_rerank_metrics(self, tmp_path):
+        """Test each rerank metric's scores and ordering, and rejection of unknown metrics."""
+        import math
+
+        core = pytest.importorskip("fast_crewai._core")
+
+        db = core.RustSQLiteWrapper(str(tmp_path / "rerank.db"), 1)
+        repeated = "deploy " * 8 + "service"
+        db.insert_memory(repeated, "{}", "2024-01-01", 1.0)
+        db.insert_memory("deploy service rollback", "{}", "2024-01-02", 1.0)
+
+        cosine = db.search_and_rerank("deploy service", 10, 2)
+        assert [r["task_description"] for r in cosine] == ["deploy service rollback", repeated]
+        assert float(cosine[0]["rerank_score"]) == pytest.approx(2 / math.sqrt(6))
+        assert float(cosine[1]["rerank_score"]) == pytest.approx(9 / math.sqrt(130))
+
+        jaccard = db.search_and_rerank("deploy service", 10, 2, rerank_metric="jaccard")
+        assert [r["task_description"] for r in jaccard] == [repeated, "deploy service rollback"]
+        assert [float(r["rerank_score"]) for r in jaccard] == [1.0, pytest.approx(2 / 3)]
+
+        # Both candidates hold both query terms, so they tie and keep their BM25 order
+        overlap = db.search_and_rerank("deploy service", 10, 1, rerank_metric="overlap")
+        assert float(overlap[0]["rerank_score"]) == 2.0
+        assert overlap[0]["id"] == db.search_memories("deploy service", 1)[0]["id"]
+
+        with pytest.raises(ValueError, match="Invalid similarity metric 'bm25'"):
+            db.search_and_rerank("deploy service", 10, 2, rerank_metric="bm25")
+
+    def test_execute_batch_errors_name_the_failing_statement(self, tmp_path):
+        """Test that batch errors report the statement index and malformed items raise TypeError."""
+        core = pytest.importorskip("fast_crewai._core")
+
+        db = core.RustSQLiteWrapper(str(tmp_path / "batch.db"), 1)
+        db.execute_update("CREATE TABLE notes (body TEXT)", {})
+        insert = ("INSERT INTO notes (body) VALUES (:body)", {":body": "kept"})
+
+        with pytest.raises(RuntimeError, match="batch query 1"):
+            db.execute_batch([insert, ("INSERT INTO missing VALUES (1)", {})])
+        with pytest.raises(TypeError, match="Batch query 1"):
+            db.execute_batch([insert, "DROP TABLE notes"])
+        with pytest.raises(TypeError, match="Batch query 0"):
+            db.execute_batch([("INSERT INTO notes (body) VALUES (:body)", {":body": 1})])
+
+        # Failed batches roll back, so only the final insert lands
+        assert db.execute_batch([insert]) == [1]
+        assert db.execute_query("SELECT count(*) AS n FROM notes", {}) == [{"n": "1"}]
+
+    def test_fts_prefix_lengths_are_bounds_checked(self, tmp_path):
+        """Test that fts_prefix rejects lengths FTS5 doesn't accept before creating the table."""
+        core = pytest.importorskip("fast_crewai._core")
+
+        for lengths in ([0], [2, 1000]):
+            with pytest.raises(ValueError, match="between 1 and 999"):
+                core.RustSQLiteWrapper(str(tmp_path / "prefix.db"), 1, fts_prefix=lengths)
+
+        db = core.RustSQLiteWrapper(str(tmp_path / "prefix.db"), 1, fts_prefix=[1, 999])
+        db.insert_memory("rust compiler", "{}", "2024-01-01", 1.0)
+        assert len(db.search_memories("ru*", 10)) == 1
+
+
+if __name__ == "__main__":
+    pytest.main([__file__])
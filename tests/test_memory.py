@@ -208,5 +208,604 @@ class TestMemoryEdgeCases:
         assert isinstance(results, list)
 
 
+class TestRustMemoryStorageCore:
+    """Test cases for the native RustMemoryStorage class."""
+
+    def test_reindex_applies_new_tokenizer_settings(self):
+        """Test that reindex rebuilds stored frequencies under new settings."""
+        core = pytest.importorskip("fast_crewai._core")
+
+        storage = core.RustMemoryStorage()
+        storage.save("Apple")
+        storage.save("apple banana")
+
+        # Lowercasing (default) makes "Apple" an exact match for the query
+        assert storage.search("apple", 2)[0] == "Apple"
+
+        # Case-sensitive tokens only match after the stored items are reindexed
+        storage.set_lowercase(False)
+        storage.reindex()
+        assert storage.search("apple", 2)[0] == "apple banana"
+        assert storage.get_all() == ["Apple", "apple banana"]
+
+    def test_bm25_scores_use_stored_document_lengths(self):
+        """Test that BM25 scores match a reference computed from document lengths."""
+        import math
+
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage()
+        documents = [
+            "rust",
+            "rust is fast",
+            "rust rust and more rust in a much longer document about rust",
+            "python is friendly",
+        ]
+        for document in documents:
+            storage.save(document)
+
+        lengths = [len(document.split()) for document in documents]
+        avg_len = sum(lengths) / len(lengths)
+        assert storage.average_document_length() == pytest.approx(avg_len)
+
+        k1, b = 1.2, 0.75
+        df = sum(1 for document in documents if "rust" in document.split())
+        idf = math.log(1 + (len(documents) - df + 0.5) / (df + 0.5))
+        expected = {}
+        for document, length in zip(documents, lengths):
+            tf = document.split().count("rust")
+            if tf:
+                expected[document] = idf * tf * (k1 + 1) / (tf + k1 * (1 - b + b * length / avg_len))
+
+        results = storage.search_bm25("rust", 10)
+        assert len(results) == len(expected)
+        for content, score in results:
+            assert score == pytest.approx(expected[content])
+        assert [score for _, score in results] == sorted(expected.values(), reverse=True)
+
+    def test_replay_journal_loads_v1_fixture(self, tmp_path):
+        """Test that a version 1 journal replays and newer versions are rejected."""
+        core = pytest.importorskip("fast_crewai._core")
+        journal = tmp_path / "memory.journal"
+        journal.write_text(
+            '{"op":"header","format_version":1}\n'
+            '{"op":"save","id":0,"content":"first note"}\n'
+            '{"op":"clear"}\n'
+            '{"op":"save","id":1,"content":"second note"}\n'
+        )
+
+        storage = core.RustMemoryStorage.replay_journal(str(journal))
+        assert storage.get_all() == ["second note"]
+        assert storage.save("third note") == 2
+
+        journal.write_text('{"op":"header","format_version":2}\n')
+        with pytest.raises(ValueError, match="unsupported format version 2"):
+            core.RustMemoryStorage.replay_journal(str(journal))
+
+    def test_search_ignores_terms_present_in_every_item(self):
+        """Test that IDF weighting stops ubiquitous words from driving the ranking."""
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage()
+        storage.save("the the the the report")
+        storage.save("the deploy")
+        storage.save("the rollback")
+
+        assert storage.search("the deploy", 1) == ["the deploy"]
+
+    def test_normalize_content_collapses_whitespace(self):
+        """Test that normalize_content trims and collapses whitespace on save."""
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage(normalize_content=True)
+        storage.save("  deploy \t the\n\nservice  ")
+        assert storage.get_all() == ["deploy the service"]
+
+        raw = core.RustMemoryStorage()
+        raw.save("  deploy  service ")
+        assert raw.get_all() == ["  deploy  service "]
+
+    def test_delete_removes_item_and_keeps_ids_unique(self, tmp_path):
+        """Test deleting by id, id monotonicity and journal replay of deletes."""
+        core = pytest.importorskip("fast_crewai._core")
+        journal = str(tmp_path / "memory.journal")
+        storage = core.RustMemoryStorage(journal_path=journal)
+        first = storage.save("deploy the service")
+        second = storage.save("rotate the keys")
+
+        assert storage.delete(second) is True
+        assert storage.delete(second) is False
+        assert storage.get_all() == ["deploy the service"]
+        assert storage.save("rotate the keys again") == second + 1
+        assert storage.search("rotate", 1) == ["rotate the keys again"]
+
+        replayed = core.RustMemoryStorage.replay_journal(journal)
+        assert replayed.get_all() == ["deploy the service", "rotate the keys again"]
+        assert replayed.delete(first) is True
+        assert replayed.get_all() == ["rotate the keys again"]
+
+    def test_search_with_activation_boosts_recalled_items(self):
+        """Test that frequently returned items gain rank as activation_weight grows."""
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage()
+        storage.save("deploy the payment service")
+        storage.save("deploy notes")
+        storage.save("unrelated filler")
+
+        for _ in range(3):
+            storage.search("payment", 1)
+        # Read-only searches leave the access counts alone
+        for _ in range(5):
+            storage.search("unrelated filler", 1, record_access=False)
+            storage.search_full("unrelated filler", 1, record_access=False)
+
+        plain = storage.search_with_activation("deploy notes", 3, 0.0, record_access=False)
+        assert [content for content, _ in plain] == storage.search("deploy notes", 3)
+
+        boosted = storage.search_with_activation("deploy notes", 1, 0.9, record_access=False)
+        assert boosted[0][0] == "deploy the payment service"
+
+    def test_save_to_file_round_trips_items_and_ids(self, tmp_path):
+        """Test that a saved store reloads with its ids and keeps retired ids unused."""
+        core = pytest.importorskip("fast_crewai._core")
+        path = str(tmp_path / "memory.json")
+        storage = core.RustMemoryStorage()
+        storage.save("deploy the service")
+        storage.save("rotate the keys")
+        retired = storage.save("temporary note")
+        storage.delete(retired)
+        storage.save_to_file(path)
+
+        restored = core.RustMemoryStorage()
+        restored.save("replaced on load")
+        restored.load_from_file(path)
+        assert restored.get_all() == ["deploy the service", "rotate the keys"]
+        assert restored.search("rotate", 1) == ["rotate the keys"]
+        assert restored.save("after load") == retired + 1
+
+    def test_stopwords_are_dropped_from_items_and_queries(self):
+        """Test custom and built-in English stopword filtering."""
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage(stopwords=["Deploy"], english_stopwords=True)
+        storage.save("The deploy of the service")
+        assert storage.term_frequencies(0) == [("service", 1.0)]
+        assert storage.search("the deploy", 1, min_score=0.01) == []
+
+        plain = core.RustMemoryStorage()
+        plain.save("The deploy of the service")
+        assert plain.vocabulary_size() == 4
+
+    def test_tokenize_matches_store_tokenization(self):
+        """Test the module-level tokenize helper against the store's defaults and options."""
+        core = pytest.importorskip("fast_crewai._core")
+        assert core.tokenize("Deploy the service. Then: deploy (again)!") == [
+            "deploy", "the", "service", "then", "deploy", "again"
+        ]
+        assert core.tokenize("Deploy the API", lowercase=False, english_stopwords=True) == [
+            "Deploy", "API"
+        ]
+        assert core.tokenize("a an deploy", min_token_len=3) == ["deploy"]
+
+    def test_concurrent_searches_and_saves(self):
+        """Test that searches from several threads run alongside writes."""
+        import threading
+
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage()
+        for i in range(50):
+            storage.save(f"deploy note {i}")
+
+        errors = []
+
+        def searcher():
+            try:
+                for _ in range(20):
+                    assert len(storage.search("deploy", 5)) == 5
+            except Exception as e:  # pragma: no cover - reported below
+                errors.append(e)
+
+        threads = [threading.Thread(target=searcher) for _ in range(4)]
+        for thread in threads:
+            thread.start()
+        for i in range(20):
+            storage.save(f"rollback note {i}")
+        for thread in threads:
+            thread.join()
+
+        assert errors == []
+        assert len(storage.get_all()) == 70
+
+    def test_search_full_returns_ids_and_scores(self):
+        """Test that search_full ranks like search and exposes ids usable with delete."""
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage()
+        storage.save("deploy the payment service")
+        rollback = storage.save("rollback the payment service")
+        storage.save("unrelated filler")
+
+        results = storage.search_full("rollback payment", 2)
+        assert [content for _, content, _ in results] == storage.search("rollback payment", 2)
+        assert results[0][0] == rollback
+        assert results[0][2] > results[1][2]
+
+        assert storage.delete(results[0][0]) is True
+        assert storage.search_full("rollback payment", 1)[0][1] == "deploy the payment service"
+
+    def test_min_docs_for_idf_switches_scoring_at_threshold(self):
+        """Test that scoring is plain TF cosine below min_docs_for_idf and TF-IDF from it."""
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage(min_docs_for_idf=3)
+        only_the = storage.save("the the the")
+        storage.save("the deploy notes")
+
+        scores = {item_id: score for item_id, _, score in storage.search_full("the deploy", 5)}
+        assert scores[only_the] == pytest.approx(2 ** -0.5)
+
+        # The third item reaches the threshold; "the" is then in every item and weighs
+        # ln(2) against ln(4) for "deploy"
+        storage.save("the rollback")
+        scores = {item_id: score for item_id, _, score in storage.search_full("the deploy", 5)}
+        assert scores[only_the] == pytest.approx(5 ** -0.5)
+
+        storage.set_min_docs_for_idf(4)
+        scores = {item_id: score for item_id, _, score in storage.search_full("the deploy", 5)}
+        assert scores[only_the] == pytest.approx(2 ** -0.5)
+
+    def test_central_memories_follows_min_docs_for_idf(self):
+        """Test that centrality uses plain TF cosine below min_docs_for_idf, like search."""
+        import math
+
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage(min_docs_for_idf=100)
+        first = storage.save("deploy service")
+        second = storage.save("deploy notes")
+        third = storage.save("rollback")
+
+        central = storage.central_memories(3)
+        assert [item_id for item_id, _ in central] == [first, second, third]
+        assert [score for _, score in central] == [pytest.approx(0.25), pytest.approx(0.25), 0.0]
+
+        storage.set_min_docs_for_idf(0)
+        shared, distinct = math.log(2.5) ** 2, math.log(4) ** 2
+        assert storage.central_memories(1)[0][1] == pytest.approx(shared / (shared + distinct) / 2)
+
+    def test_len_and_is_empty_track_saves_and_deletes(self):
+        """Test counting stored items without fetching them."""
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage()
+        assert storage.len() == 0
+        assert storage.is_empty() is True
+
+        first = storage.save("deploy the service")
+        storage.save("rotate the keys")
+        assert storage.len() == 2
+        assert storage.is_empty() is False
+
+        storage.delete(first)
+        assert storage.len() == 1
+        storage.clear()
+        assert storage.is_empty() is True
+
+    def test_save_batch_assigns_contiguous_ids(self, tmp_path):
+        """Test that a batch save matches individual saves and is journaled."""
+        core = pytest.importorskip("fast_crewai._core")
+        journal = str(tmp_path / "memory.journal")
+        storage = core.RustMemoryStorage(journal_path=journal)
+        storage.save("existing note")
+
+        ids = storage.save_batch(["deploy the service", "rotate the keys", "rollback"])
+        assert ids == [1, 2, 3]
+        assert storage.save("after batch") == 4
+        assert storage.search("rotate keys", 1) == ["rotate the keys"]
+        assert storage.save_batch([]) == []
+
+        replayed = core.RustMemoryStorage.replay_journal(journal)
+        assert replayed.get_all() == storage.get_all()
+
+    def test_federated_search_merges_shards_by_score(self):
+        """Test that results from several stores are merged into one global ranking."""
+        core = pytest.importorskip("fast_crewai._core")
+        east = core.RustMemoryStorage()
+        west = core.RustMemoryStorage()
+        east.save_batch(["deploy the payment service", "filler note one"])
+        west.save_batch(["rollback the payment service", "payment service rollback plan", "unrelated filler"])
+
+        results = core.federated_search([east, west], "rollback plan", 2)
+        assert [content for content, _ in results] == [
+            "payment service rollback plan",
+            "rollback the payment service",
+        ]
+        assert results[0][1] >= results[1][1]
+        assert core.federated_search([], "rollback", 5) == []
+
+    def test_dedup_returns_existing_ids(self):
+        """Test exact and similarity-based deduplication on save."""
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage(dedup=True)
+        first = storage.save("deploy the service")
+        assert storage.save("deploy the service") == first
+        assert storage.save("the service deploy") != first
+        assert storage.save_batch(["rotate keys", "deploy the service", "rotate keys"]) == [2, first, 2]
+        assert storage.len() == 3
+
+        loose = core.RustMemoryStorage(dedup_threshold=0.75)
+        original = loose.save("deploy the payment service today")
+        assert loose.save("deploy the payment service now") == original
+        assert loose.save("rollback plan") != original
+
+        with pytest.raises(ValueError):
+            core.RustMemoryStorage(dedup_threshold=1.5)
+        plain = core.RustMemoryStorage()
+        assert plain.save("same") != plain.save("same")
+
+    def test_cjk_mode_indexes_character_bigrams(self):
+        """Test that CJK runs are tokenized as bigrams alongside ordinary words."""
+        core = pytest.importorskip("fast_crewai._core")
+        assert core.tokenize("東京都に行く", cjk_mode=True) == ["東京", "京都", "都に", "に行", "行く"]
+        assert core.tokenize("Rust語で書いた。API", cjk_mode=True) == ["rust", "語で", "で書", "書い", "いた", "api"]
+        assert core.tokenize("東京都", cjk_mode=False) == ["東京都"]
+
+        storage = core.RustMemoryStorage(cjk_mode=True)
+        storage.save("東京都の天気予報")
+        storage.save("大阪の美味しい食べ物")
+        storage.save("deploy the service")
+        assert storage.search("天気", 1) == ["東京都の天気予報"]
+        assert storage.search("食べ物", 1) == ["大阪の美味しい食べ物"]
+
+    def test_items_without_query_terms_score_zero(self):
+        """Test that items sharing no query term still rank, with a score of exactly 0."""
+        core = pytest.importorskip("fast_crewai._core")
+        for storage in (core.RustMemoryStorage(), core.RustMemoryStorage(min_docs_for_idf=100)):
+            storage.save("deploy the payment service")
+            unrelated = storage.save("quarterly planning notes")
+            storage.save("rollback plan")
+
+            results = storage.search_full("payment rollback", 3)
+            assert len(results) == 3
+            assert results[-1][0] == unrelated
+            assert results[-1][2] == 0.0
+            assert all(score > 0.0 for _, _, score in results[:2])
+
+    def test_export_vectors_dense_and_sparse(self):
+        """Test exporting TF-IDF vectors aligned to the store's vocabulary."""
+        import math
+
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage()
+        first = storage.save("deploy deploy service")
+        second = storage.save("rollback service")
+
+        vocabulary, dense = storage.export_vectors()
+        assert vocabulary == ["deploy", "rollback", "service"]
+        assert [item_id for item_id, _ in dense] == [first, second]
+        assert dense[0][1] == [pytest.approx(2 * math.log(3)), 0.0, pytest.approx(math.log(2))]
+        assert dense[1][1] == [0.0, pytest.approx(math.log(3)), pytest.approx(math.log(2))]
+
+        sparse_vocabulary, sparse = storage.export_vectors_sparse()
+        assert sparse_vocabulary == vocabulary
+        assert sparse[0][0] == first
+        assert sparse[0][1] == [(0, pytest.approx(2 * math.log(3))), (2, pytest.approx(math.log(2)))]
+        assert sparse[1][1] == [(1, pytest.approx(math.log(3))), (2, pytest.approx(math.log(2)))]
+
+    def test_cosine_scores_match_reference_after_reload(self, tmp_path):
+        """Test plain cosine scores against a reference, for saved and reloaded items."""
+        import math
+        from collections import Counter
+
+        core = pytest.importorskip("fast_crewai._core")
+
+        def cosine(a, b):
+            a, b = Counter(core.tokenize(a)), Counter(core.tokenize(b))
+            dot = sum(a[term] * b[term] for term in a)
+            return dot / (math.sqrt(sum(v * v for v in a.values())) * math.sqrt(sum(v * v for v in b.values())))
+
+        contents = ["deploy deploy the service now", "rollback the deploy", "notes"]
+        query = "deploy the deploy notes"
+        storage = core.RustMemoryStorage(min_docs_for_idf=100)
+        ids = [storage.save(content) for content in contents]
+        path = str(tmp_path / "memory.json")
+        storage.save_to_file(path)
+        reloaded = core.RustMemoryStorage(min_docs_for_idf=100)
+        reloaded.load_from_file(path)
+
+        for store in (storage, reloaded):
+            scores = {item_id: score for item_id, _, score in store.search_full(query, 5)}
+            for item_id, content in zip(ids, contents):
+                assert scores[item_id] == pytest.approx(cosine(query, content), abs=1e-12)
+
+    def test_cached_norms_follow_reindex(self):
+        """Test that reindexing refreshes each item's cached norm along with its terms."""
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage(min_docs_for_idf=100)
+        item_id = storage.save("Apple apple APPLE")
+
+        scores = {found: score for found, _, score in storage.search_full("apple", 1)}
+        assert scores[item_id] == pytest.approx(1.0)
+
+        # Case-sensitive, the item has three distinct terms: a stale norm of 3 would give 1/3
+        storage.set_lowercase(False)
+        storage.reindex()
+        scores = {found: score for found, _, score in storage.search_full("apple", 1)}
+        assert scores[item_id] == pytest.approx(3 ** -0.5)
+
+    def test_hybrid_recall_merges_memory_and_sqlite(self, tmp_path):
+        """Test recalling from both tiers with normalized scores and deduplication."""
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage()
+        storage.save("Deploy the  payment service")
+        storage.save("rotate the api keys")
+        storage.save("unrelated notes")
+        db = core.RustSQLiteWrapper(str(tmp_path / "recall.db"), 1)
+        db.insert_memory("deploy the payment service", "{}", "2024-01-01", 1.0)
+        db.insert_memory("payment service rollback plan", "{}", "2024-01-02", 1.0)
+
+        results = core.hybrid_recall(storage, db, "deploy payment service?", 5)
+        assert [(r["source"], r["content"]) for r in results] == [
+            ("memory", "Deploy the  payment service"),
+            ("sqlite", "payment service rollback plan"),
+        ]
+        assert float(results[0]["relevance"]) == 1.0
+        assert 0.0 < float(results[1]["relevance"]) <= 1.0
+        assert "similarity" in results[0] and "rank" in results[1]
+
+        assert len(core.hybrid_recall(storage, db, "deploy payment service", 1)) == 1
+        assert core.hybrid_recall(storage, db, "   ", 5) == []
+
+    def test_hybrid_recall_keeps_small_memory_tier(self, tmp_path):
+        """Test that a one- or two-item store still contributes its matches."""
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage()
+        storage.save("deploy the payment service")
+        db = core.RustSQLiteWrapper(str(tmp_path / "small.db"), 1)
+        db.insert_memory("payment service rollback plan", "{}", "2024-01-02", 1.0)
+
+        results = core.hybrid_recall(storage, db, "payment", 5)
+        assert [r["source"] for r in results] == ["memory", "sqlite"]
+        assert float(results[0]["relevance"]) == 1.0
+        assert float(results[0]["similarity"]) > 0.0
+
+        # Both items contain "payment", which still weighs above 0
+        storage.save("payment reminders")
+        results = core.hybrid_recall(storage, db, "payment", 5)
+        assert [r["content"] for r in results if r["source"] == "memory"] == [
+            "payment reminders",
+            "deploy the payment service",
+        ]
+        assert [r["content"] for r in core.hybrid_recall(storage, db, "rollback", 5)] == [
+            "payment service rollback plan"
+        ]
+
+    def test_hybrid_recall_fills_limit_past_duplicates(self, tmp_path):
+        """Test that dropping duplicates doesn't leave hybrid results short of limit."""
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage()
+        storage.save("deploy service")
+        db = core.RustSQLiteWrapper(str(tmp_path / "dupes.db"), 1)
+        db.insert_memory("Deploy Service", "{}", "2024-01-01", 1.0)
+        db.insert_memory("deploy  service", "{}", "2024-01-02", 1.0)
+        db.insert_memory("deploy service rollback checklist", "{}", "2024-01-03", 1.0)
+
+        results = core.hybrid_recall(storage, db, "deploy service", 2)
+        assert [r["content"] for r in results] == ["deploy service", "deploy service rollback checklist"]
+        assert [r["source"] for r in results] == ["memory", "sqlite"]
+
+    def test_exact_matches_score_above_zero_with_default_idf(self):
+        """Test that smoothed IDF keeps terms found in every item from scoring 0."""
+        import math
+
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage()
+        only = storage.save("deploy service")
+        assert storage.search_full("deploy service", 1) == [(only, "deploy service", pytest.approx(1.0))]
+
+        storage.save("deploy notes")
+        scores = {item_id: score for item_id, _, score in storage.search_full("deploy", 2)}
+        shared, distinct = math.log(2) ** 2, math.log(3) ** 2
+        assert scores[only] == pytest.approx(math.sqrt(shared / (shared + distinct)))
+
+    def test_replay_journal_restores_creation_times(self, tmp_path):
+        """Test that replayed items keep their journaled created_at instead of the replay time."""
+        import json
+
+        core = pytest.importorskip("fast_crewai._core")
+        journal = tmp_path / "memory.journal"
+        journal.write_text(
+            '{"op":"header","format_version":1}\n'
+            '{"op":"save","id":0,"content":"old note","created_at":1000}\n'
+            '{"op":"save","id":1,"content":"legacy note"}\n'
+        )
+
+        storage = core.RustMemoryStorage.replay_journal(str(journal))
+        storage.compact_journal()
+        records = [json.loads(line) for line in journal.read_text().splitlines()[1:]]
+        assert records[0]["created_at"] == 1000
+        assert records[1]["created_at"] > 1000
+
+    def test_load_from_file_closes_open_cursors(self, tmp_path):
+        """Test that a cursor opened before load_from_file can't fetch from the new items."""
+        core = pytest.importorskip("fast_crewai._core")
+        path = str(tmp_path / "memory.json")
+        source = core.RustMemoryStorage()
+        source.save("loaded item")
+        source.save_to_file(path)
+
+        storage = core.RustMemoryStorage()
+        storage.save("first")
+        storage.save("second")
+        cursor = storage.open_cursor()
+        assert storage.fetch(cursor, 1) == ["first"]
+
+        storage.load_from_file(path)
+        with pytest.raises(ValueError, match="not found or already closed"):
+            storage.fetch(cursor, 1)
+        assert storage.fetch(storage.open_cursor(), 5) == ["loaded item"]
+
+    def test_search_min_score_filters_and_rejects_non_finite(self):
+        """Test that min_score drops lower-scoring items and must be a finite number."""
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage()
+        storage.save("deploy service")
+        storage.save("deploy notes")
+        storage.save("quarterly planning")
+
+        scores = [score for _, _, score in storage.search_full("deploy service", 3)]
+        assert scores[0] > scores[1] > scores[2] == 0.0
+
+        assert storage.search("deploy service", 3, min_score=scores[1]) == ["deploy service", "deploy notes"]
+        assert storage.search("deploy service", 3, min_score=1e-9) == ["deploy service", "deploy notes"]
+        assert storage.search("deploy service", 3, min_score=(scores[0] + scores[1]) / 2) == ["deploy service"]
+        for bad in (float("nan"), float("inf"), float("-inf")):
+            with pytest.raises(ValueError, match="min_score must be finite"):
+                storage.search("deploy service", 3, min_score=bad)
+
+    def test_load_from_file_reads_unversioned_file(self, tmp_path):
+        """Test that a memory file written before versioning (no format_version) still loads."""
+        import json
+
+        core = pytest.importorskip("fast_crewai._core")
+        path = tmp_path / "memory.json"
+        storage = core.RustMemoryStorage()
+        storage.save("deploy the service")
+        storage.save_to_file(str(path))
+        legacy = json.loads(path.read_text())
+        del legacy["format_version"]
+        path.write_text(json.dumps(legacy))
+
+        restored = core.RustMemoryStorage()
+        restored.load_from_file(str(path))
+        assert restored.get_all() == ["deploy the service"]
+        assert restored.search("deploy", 1) == ["deploy the service"]
+
+    def test_replay_journal_restores_scoring_and_dedup_settings(self, tmp_path):
+        """Test passing min_docs_for_idf and dedup options through replay_journal."""
+        core = pytest.importorskip("fast_crewai._core")
+        journal = str(tmp_path / "memory.journal")
+        storage = core.RustMemoryStorage(journal_path=journal, min_docs_for_idf=100, dedup_threshold=0.75)
+        only_the = storage.save("the the the")
+        storage.save("the deploy notes")
+
+        replayed = core.RustMemoryStorage.replay_journal(journal, min_docs_for_idf=100, dedup_threshold=0.75)
+        scores = {item_id: score for item_id, _, score in replayed.search_full("the deploy", 2)}
+        assert scores[only_the] == pytest.approx(2 ** -0.5)
+        assert replayed.save("the deploy notes today") == only_the + 1
+        assert replayed.save("the the the") == only_the
+
+        with pytest.raises(ValueError, match="dedup_threshold"):
+            core.RustMemoryStorage.replay_journal(journal, dedup_threshold=2.0)
+
+    def test_export_vectors_uses_plain_counts_below_min_docs_for_idf(self):
+        """Test that exported vectors follow search's TF fallback in small stores."""
+        import math
+
+        core = pytest.importorskip("fast_crewai._core")
+        storage = core.RustMemoryStorage(min_docs_for_idf=3)
+        storage.save("deploy deploy service")
+        storage.save("rollback service")
+
+        _, dense = storage.export_vectors()
+        assert dense[0][1] == [2.0, 0.0, 1.0]
+        assert storage.export_vectors_sparse()[1][1][1] == [(1, 1.0), (2, 1.0)]
+
+        storage.set_min_docs_for_idf(0)
+        assert storage.export_vectors()[1][0][1][2] == pytest.approx(math.log(2))
+
+
 if __name__ == "__main__":
     pytest.main([__file__])
@@ -0,0 +1,103 @@
+"""
+Tests for serialization components.
+"""
+
+import pytest
+
+
+class TestConversationCore:
+    """Test cases for the native Conversation class."""
+
+    def test_from_json_loads_v1_fixture(self):
+        """Test that a version 1 conversation loads and newer versions are rejected."""
+        core = pytest.importorskip("fast_crewai._core")
+        fixture = (
+            '{"format_version": 1, "messages": ['
+            '{"id": "m1", "sender": "planner", "recipient": "worker", "content": "start",'
+            ' "timestamp": 1},'
+            '{"id": "m2", "sender": "worker", "recipient": "planner", "content": "done",'
+            ' "timestamp": 2, "reply_to": "m1"}]}'
+        )
+
+        conversation = core.Conversation.from_json(fixture)
+        assert [message.id for message in conversation.thread("m1")] == ["m1", "m2"]
+
+        with pytest.raises(ValueError, match="unsupported format version 2"):
+            core.Conversation.from_json(fixture.replace('"format_version": 1', '"format_version": 2'))
+
+    def test_agent_message_reply_to_round_trips(self):
+        """Test that reply_to survives JSON and defaults to None for older messages."""
+        core = pytest.importorskip("fast_crewai._core")
+        reply = core.AgentMessage("m2", "worker", "planner", "done", 2, reply_to="m1")
+        assert core.AgentMessage.from_json(reply.to_json()).reply_to == "m1"
+
+        reply.reply_to = None
+        assert core.AgentMessage.from_json(reply.to_json()).reply_to is None
+
+        legacy = core.AgentMessage.from_json(
+            '{"id": "m1", "sender": "planner", "recipient": "worker", "content": "start", "timestamp": 1}'
+        )
+        assert legacy.reply_to is None
+
+    def test_agent_message_msgpack_round_trips(self):
+        """Test MessagePack round-tripping, including large integer timestamps."""
+        core = pytest.importorskip("fast_crewai._core")
+        timestamp = 2**63 + 12345
+        message = core.AgentMessage("m2", "worker", "planner", "done \u2713", timestamp, reply_to="m1")
+        packed = message.to_msgpack()
+        assert isinstance(packed, bytes)
+        assert len(packed) < len(message.to_json())
+
+        restored = core.AgentMessage.from_msgpack(packed)
+        assert (restored.id, restored.sender, restored.recipient, restored.content) == (
+            "m2",
+            "worker",
+            "planner",
+            "done \u2713",
+        )
+        assert restored.timestamp == timestamp
+        assert restored.reply_to == "m1"
+
+        with pytest.raises(RuntimeError, match="Failed to deserialize from MessagePack"):
+            core.AgentMessage.from_msgpack(b"\xc1")
+
+    def test_agent_message_dict_round_trips(self):
+        """Test converting messages to and from dicts without JSON."""
+        import json
+
+        core = pytest.importorskip("fast_crewai._core")
+        message = core.AgentMessage("m2", "worker", "planner", "done", 2**40, reply_to="m1")
+        as_dict = message.to_dict()
+        assert as_dict == json.loads(message.to_json())
+
+        restored = core.AgentMessage.from_dict(as_dict)
+        assert restored.to_json() == message.to_json()
+
+        del as_dict["reply_to"]
+        as_dict["extra"] = 1
+        assert core.AgentMessage.from_dict(as_dict).reply_to is None
+        del as_dict["sender"]
+        with pytest.raises(ValueError, match="Missing field 'sender'"):
+            core.AgentMessage.from_dict(as_dict)
+
+    def test_agent_message_equality_hash_and_fingerprint(self):
+        """Test id-based equality and hashing, and id-independent content fingerprints."""
+        core = pytest.importorskip("fast_crewai._core")
+        first = core.AgentMessage("m1", "planner", "worker", "start", 1)
+        resent = core.AgentMessage("m1", "planner", "worker", "edited", 2)
+        copy = core.AgentMessage("m2", "planner", "worker", "start", 3, reply_to="m0")
+
+        assert first == resent and hash(first) == hash(resent)
+        assert first != copy
+        assert len({first, resent, copy}) == 2
+        assert first != "m1"
+
+        assert first.content_fingerprint() == copy.content_fingerprint()
+        assert first.content_fingerprint() != resent.content_fingerprint()
+        shifted = core.AgentMessage("m3", "plannerw", "orker", "start", 4)
+        assert shifted.content_fingerprint() != first.content_fingerprint()
+        assert len(first.content_fingerprint()) == 16
+
+
+if __name__ == "__main__":
+    pytest.main([__file__])
@@ -216,5 +216,229 @@ class TestTaskEdgeCases:
         assert executor is not None
 
 
+class TestRustTaskExecutorCore:
+    """Test cases for the native RustTaskExecutor class."""
+
+    def _terminal_executor(self, core, state):
+        executor = core.RustTaskExecutor()
+        executor.register_task("task", [])
+        executor.mark_started("task")
+        if state == "completed":
+            executor.mark_completed("task", "done")
+        else:
+            executor.mark_failed("task", "timed out")
+        return executor
+
+    def test_completed_to_completed_rejected(self):
+        """Test that a completed task cannot be completed again."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = self._terminal_executor(core, "completed")
+
+        with pytest.raises(ValueError, match="already completed"):
+            executor.mark_completed("task", "late result")
+        assert executor.get_result("task") == "done"
+
+    def test_completed_to_failed_rejected(self):
+        """Test that a completed task cannot be marked failed."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = self._terminal_executor(core, "completed")
+
+        with pytest.raises(ValueError, match="already completed"):
+            executor.mark_failed("task", "late error")
+
+    def test_failed_to_completed_rejected(self):
+        """Test that a late result cannot mask a failure."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = self._terminal_executor(core, "failed")
+
+        with pytest.raises(ValueError, match="already failed"):
+            executor.mark_completed("task", "late result")
+        assert executor.get_result("task") is None
+
+    def test_failed_to_failed_rejected(self):
+        """Test that a failed task cannot be failed again."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = self._terminal_executor(core, "failed")
+
+        with pytest.raises(ValueError, match="already failed"):
+            executor.mark_failed("task", "second error")
+
+    def test_force_overrides_terminal_state(self):
+        """Test that force=True allows recovering a terminal task."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = self._terminal_executor(core, "failed")
+
+        executor.mark_completed("task", "recovered", force=True)
+        assert executor.get_result("task") == "recovered"
+
+    def test_checkpoint_resume_skips_completed_tasks(self, tmp_path):
+        """Test that a restored checkpoint keeps results and only reruns unfinished tasks."""
+        core = pytest.importorskip("fast_crewai._core")
+        checkpoint = tmp_path / "tasks.json"
+        executor = core.RustTaskExecutor()
+        executor.register_task("fetch", [])
+        executor.register_task("summarize", ["fetch"])
+        executor.mark_started("fetch")
+        executor.mark_completed("fetch", "raw data")
+
+        executor.execute_concurrent_tasks(
+            ["fetch", "summarize"], checkpoint_path=str(checkpoint), checkpoint_every=1
+        )
+
+        resumed = core.RustTaskExecutor.from_json(checkpoint.read_text())
+        assert resumed.get_result("fetch") == "raw data"
+        assert resumed.execute_concurrent_tasks(["fetch", "summarize"], skip_completed=True) == ["summarize"]
+
+    def test_max_depth_rejects_overly_deep_chain(self):
+        """Test that registering a task past max_depth fails and names the chain."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustTaskExecutor(max_depth=2)
+        executor.register_task("a", [])
+        executor.register_task("b", ["a"])
+        executor.register_task("c", ["b"])
+
+        with pytest.raises(ValueError, match="d -> c -> b -> a"):
+            executor.register_task("d", ["c"])
+
+        # The rejected task is not registered and shallower tasks are still accepted
+        assert executor.get_ready_tasks() == ["a"]
+        executor.register_task("e", ["b"])
+        assert executor.get_stats()["tasks_scheduled"] == 4
+
+    def test_from_json_loads_v1_fixture(self):
+        """Test that a version 1 task graph loads and newer versions are rejected."""
+        core = pytest.importorskip("fast_crewai._core")
+        fixture = (
+            '{"format_version": 1, "tasks": {'
+            '"fetch": {"dependencies": [], "state": "Completed", "result": "data", "error": null},'
+            '"parse": {"dependencies": ["fetch"], "state": "Pending", "result": null, "error": null}}}'
+        )
+
+        executor = core.RustTaskExecutor.from_json(fixture)
+        assert executor.get_result("fetch") == "data"
+        assert executor.get_ready_tasks() == ["parse"]
+
+        with pytest.raises(ValueError, match="unsupported format version 2"):
+            core.RustTaskExecutor.from_json(fixture.replace('"format_version": 1', '"format_version": 2'))
+
+    def test_runtime_flavor_reports_multi_thread(self):
+        """Test that a normally constructed executor reports the multi-thread runtime."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustTaskExecutor()
+        assert executor.runtime_flavor() == "multi_thread"
+        executor.register_task("a", [])
+        assert executor.execute_concurrent_tasks(["a"]) == ["a"]
+
+    def test_json_results_round_trip(self):
+        """Test storing and reading structured task results."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustTaskExecutor()
+        for task_id in ("structured", "plain", "pending"):
+            executor.register_task(task_id, [])
+
+        executor.mark_completed_json("structured", {"score": 0.5, "tags": ["a", "b"], "ok": True})
+        assert executor.get_result_json("structured") == {"score": 0.5, "tags": ["a", "b"], "ok": True}
+        assert '"score": 0.5' in executor.get_result("structured")
+
+        executor.mark_completed("plain", "not json")
+        with pytest.raises(ValueError, match="not valid JSON"):
+            executor.get_result_json("plain")
+        assert executor.get_result_json("pending") is None
+        with pytest.raises(ValueError):
+            executor.mark_completed_json("pending", {"bad": object()})
+
+    def test_cancel_all_running_stops_in_flight_tasks(self):
+        """Test cancelling every running task while leaving the rest untouched."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustTaskExecutor()
+        for task_id in ("done", "crawl", "index", "waiting"):
+            executor.register_task(task_id, [])
+        executor.register_task("report", ["crawl"])
+        executor.mark_completed("done", "ok")
+        executor.mark_started("index")
+        executor.mark_started("crawl")
+
+        assert executor.cancel_all_running() == ["crawl", "index"]
+        assert executor.cancel_all_running() == []
+        assert executor.get_stats()["tasks_cancelled"] == 2
+        assert executor.get_result("done") == "ok"
+        assert executor.get_ready_tasks() == ["waiting"]
+
+        with pytest.raises(ValueError, match="already cancelled"):
+            executor.mark_completed("crawl", "late")
+        executor.mark_completed("crawl", "retried", force=True)
+        assert sorted(executor.get_ready_tasks()) == ["report", "waiting"]
+
+    def test_mark_started_rejects_terminal_tasks(self):
+        """Test that a late start can't reopen a completed, failed or cancelled task."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustTaskExecutor()
+        for task_id in ("done", "broken", "stopped"):
+            executor.register_task(task_id, [])
+        executor.mark_completed("done", "result")
+        executor.mark_failed("broken", "boom")
+        executor.mark_started("stopped")
+        executor.cancel_all_running()
+
+        for task_id, state in (("done", "completed"), ("broken", "failed"), ("stopped", "cancelled")):
+            with pytest.raises(ValueError, match=f"already {state} and cannot be marked running"):
+                executor.mark_started(task_id)
+        with pytest.raises(ValueError, match="already completed"):
+            executor.mark_completed("done", "late")
+        assert executor.get_result("done") == "result"
+        assert executor.get_stats()["tasks_completed"] == 1
+
+        executor.mark_started("done", force=True)
+        executor.mark_completed("done", "rerun")
+        assert executor.get_result("done") == "rerun"
+
+    def test_execute_concurrent_returns_every_id_without_skip(self):
+        """Test that completed tasks are returned, in order, unless skip_completed is set."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustTaskExecutor()
+        for task_id in ("fetch", "parse", "report"):
+            executor.register_task(task_id, [])
+        executor.mark_completed("parse", "parsed")
+
+        assert executor.execute_concurrent_tasks(["report", "parse", "fetch"]) == ["report", "parse", "fetch"]
+        assert executor.execute_concurrent_tasks(["report", "parse", "fetch"], skip_completed=True) == [
+            "report",
+            "fetch",
+        ]
+
+    def test_has_ready_tasks_matches_get_ready_tasks(self):
+        """Test the ready check with no tasks, an unmet dependency and a ready task."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustTaskExecutor()
+        assert executor.has_ready_tasks() is False
+
+        executor.register_task("fetch", [])
+        executor.register_task("parse", ["fetch"])
+        executor.mark_started("fetch")
+        assert executor.get_ready_tasks() == []
+        assert executor.has_ready_tasks() is False
+
+        executor.mark_completed("fetch", "data")
+        assert executor.get_ready_tasks() == ["parse"]
+        assert executor.has_ready_tasks() is True
+
+    def test_from_json_loads_unversioned_graph(self):
+        """Test that a task graph written before versioning (no format_version) still loads."""
+        import json
+
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustTaskExecutor()
+        executor.register_task("fetch", [])
+        executor.register_task("parse", ["fetch"])
+        executor.mark_completed("fetch", "data")
+        legacy = json.loads(executor.to_json())
+        del legacy["format_version"]
+
+        restored = core.RustTaskExecutor.from_json(json.dumps(legacy))
+        assert restored.get_result("fetch") == "data"
+        assert restored.get_ready_tasks() == ["parse"]
+        assert json.loads(restored.to_json())["format_version"] == 1
+
+
 if __name__ == "__main__":
     pytest.main([__file__])
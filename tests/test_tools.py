@@ -3,6 +3,7 @@ Tests for tool execution components.
 """
 
 import json
+import threading
 import time
 
 import pytest
@@ -241,5 +242,473 @@ class TestToolPerformance:
         assert serialization_time < 5.0  # 100 serializations in under 5 seconds
 
 
+class TestRustToolExecutorCore:
+    """Test cases for the native RustToolExecutor class."""
+
+    def test_tool_version_bump_misses_cache(self):
+        """Test that bumping a tool's version makes old cached results unreachable."""
+        core = pytest.importorskip("fast_crewai._core")
+
+        executor = core.RustToolExecutor(10)
+        executor.register_tool_version("search", "1.0")
+        executor.cache_result("search", '{"q": "rust"}', "v1 result")
+        assert executor.get_cached("search", '{"q": "rust"}') == "v1 result"
+
+        executor.register_tool_version("search", "1.1")
+        assert executor.get_cached("search", '{"q": "rust"}') is None
+
+    def test_get_cached_batch_mixes_hits_and_misses(self):
+        """Test that batched lookups align with the inputs and count hits and misses."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10)
+        executor.register_tool_version("search", "2")
+        executor.cache_result("search", "rust", "rust results")
+        executor.cache_result("fetch", "page-1", "page body")
+
+        results = executor.get_cached_batch(
+            [("search", "rust"), ("search", "python"), ("fetch", "page-1"), ("fetch", "page-2")]
+        )
+
+        assert results == ["rust results", None, "page body", None]
+        stats = executor.get_stats()
+        assert stats["cache_hits"] == 2
+        assert stats["cache_misses"] == 2
+        assert executor.cache_analytics()["search"]["hits"] == 1
+
+    def test_schedule_purge_clears_only_that_tool(self):
+        """Test that a scheduled purge sweeps one tool's entries and can be cancelled."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10)
+        executor.cache_result("weather", "paris", "sunny")
+        executor.cache_result("search", "rust", "rust results")
+        executor.schedule_purge("weather", 1)
+
+        time.sleep(1.3)
+        assert executor.get_cached("weather", "paris") is None
+        assert executor.get_cached("search", "rust") == "rust results"
+
+        assert executor.cancel_purge("weather") is True
+        assert executor.cancel_purge("weather") is False
+        executor.cache_result("weather", "paris", "rainy")
+        time.sleep(1.2)
+        assert executor.get_cached("weather", "paris") == "rainy"
+
+    def test_manifest_round_trip_preserves_age_and_pins(self):
+        """Test exporting a cache manifest and warming another executor from it."""
+        core = pytest.importorskip("fast_crewai._core")
+        source = core.RustToolExecutor(10, cache_ttl_secs=60)
+        source.cache_result("search", "rust", "rust results")
+        source.cache_result("fetch", "page-1", "page body")
+        source.pin_cache("fetch", "page-1")
+
+        manifest = json.loads(source.export_manifest())
+        assert manifest["format_version"] == 1
+        assert [(e["tool"], e["args"], e["pinned"]) for e in manifest["entries"]] == [
+            ("fetch", "page-1", True),
+            ("search", "rust", False),
+        ]
+        assert len(json.loads(source.export_manifest(include_pinned=False))["entries"]) == 1
+
+        target = core.RustToolExecutor(10, cache_ttl_secs=60)
+        assert target.import_manifest(source.export_manifest()) == 2
+        assert target.get_cached("search", "rust") == "rust results"
+        assert target.get_stats()["pinned_entries"] == 1
+
+        # Entries older than the TTL are skipped unless pinned
+        manifest["entries"][1]["age_secs"] = 120
+        manifest["entries"][0]["age_secs"] = 120
+        fresh = core.RustToolExecutor(10, cache_ttl_secs=60)
+        assert fresh.import_manifest(json.dumps(manifest)) == 1
+        assert fresh.get_cached("search", "rust") is None
+        assert fresh.get_cached("fetch", "page-1") == "page body"
+
+    def test_per_tool_ttl_overrides_global(self):
+        """Test that a per-tool TTL takes precedence over the executor-wide TTL."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10, cache_ttl_secs=60)
+        executor.set_tool_ttl("prices", 1)
+        executor.cache_result("prices", "BTC", "1")
+        executor.cache_result("lookup", "BTC", "bitcoin")
+
+        time.sleep(1.1)
+        assert executor.get_cached("prices", "BTC") is None
+        assert executor.get_cached_batch([("prices", "BTC"), ("lookup", "BTC")]) == [None, "bitcoin"]
+
+        executor.set_tool_ttl("lookup", 1)
+        assert executor.cleanup_expired() == 1
+
+    def test_lru_eviction_and_cleanup_expired(self):
+        """Test that a full cache evicts the least recently used entry."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10, cache_ttl_secs=60, max_cache_entries=2)
+        executor.cache_result("search", "a", "A")
+        executor.cache_result("search", "b", "B")
+        assert executor.get_cached("search", "a") == "A"
+
+        executor.cache_result("search", "c", "C")
+        assert executor.get_cached("search", "b") is None
+        assert executor.get_cached("search", "a") == "A"
+        assert executor.get_cached("search", "c") == "C"
+
+        executor.set_tool_ttl("search", 1)
+        time.sleep(1.1)
+        assert executor.cleanup_expired() == 2
+        assert executor.cleanup_expired() == 0
+
+    def test_equivalent_json_args_share_cache_entry(self):
+        """Test that cache keys ignore JSON key order and whitespace."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10)
+        executor.cache_result("search", '{"query": "rust", "limit": 5}', "results")
+
+        assert executor.get_cached("search", '{"limit":5,"query":"rust"}') == "results"
+        assert executor.get_cached("search", '{"query": "rust", "limit": 6}') is None
+        assert executor.get_cached("fetch", '{"limit":5,"query":"rust"}') is None
+
+        # Non-JSON args still work, matched verbatim
+        executor.cache_result("echo", "plain text", "echoed")
+        assert executor.get_cached("echo", "plain text") == "echoed"
+        assert executor.get_cached("echo", "plain  text") is None
+
+    def test_validate_against_schema(self):
+        """Test structural validation of tool arguments against a registered schema."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10)
+        executor.register_schema(
+            "search",
+            json.dumps({
+                "type": "object",
+                "required": ["query"],
+                "properties": {
+                    "query": {"type": "string", "minLength": 1},
+                    "limit": {"type": "integer", "minimum": 1},
+                    "tags": {"type": "array", "items": {"enum": ["web", "news"]}},
+                },
+                "additionalProperties": False,
+            }),
+        )
+
+        assert executor.validate_against_schema("search", '{"query": "rust", "limit": 5, "tags": ["web"]}')
+        with pytest.raises(ValueError, match=r"'\$\.query': required field is missing"):
+            executor.validate_against_schema("search", '{"limit": 5}')
+        with pytest.raises(ValueError, match=r"'\$\.limit': expected integer, got string"):
+            executor.validate_against_schema("search", '{"query": "rust", "limit": "5"}')
+        with pytest.raises(ValueError, match=r"'\$\.tags\[1\]'"):
+            executor.validate_against_schema("search", '{"query": "rust", "tags": ["web", "blogs"]}')
+        with pytest.raises(ValueError, match="unexpected field"):
+            executor.validate_against_schema("search", '{"query": "rust", "page": 2}')
+        assert executor.get_stats()["validation_failures"] == 4
+
+        # Tools without a schema only get the syntax check
+        assert executor.validate_against_schema("fetch", '{"anything": true}')
+        with pytest.raises(ValueError):
+            executor.register_schema("fetch", "[1, 2]")
+
+    def test_stats_snapshot_and_diff(self):
+        """Test measuring counter deltas over a window without resetting stats."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10)
+        executor.cache_result("search", "rust", "results")
+        executor.get_cached("search", "rust")
+
+        snapshot = executor.stats_snapshot()
+        assert json.loads(snapshot)["format_version"] == 1
+
+        executor.get_cached("search", "rust")
+        executor.get_cached("search", "python")
+        executor.get_cached("fetch", "page")
+        delta = executor.diff_stats(snapshot)
+
+        assert delta["cache_hits"] == 1
+        assert delta["cache_misses"] == 2
+        assert delta["total_executions"] == 0
+        assert delta["per_tool.search.hits"] == 1
+        assert delta["per_tool.search.stores"] == 0
+        assert delta["per_tool.fetch.misses"] == 1
+        assert delta["cache_hit_rate_percent"] == pytest.approx(100 / 3)
+        assert executor.get_stats()["cache_hits"] == 2
+
+    def test_reset_stats_starts_fresh_window(self):
+        """Test that reset_stats zeroes counters but keeps cached results."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10)
+        executor.cache_result("search", "rust", "results")
+        executor.get_cached("search", "rust")
+        executor.get_cached("search", "python")
+        time.sleep(1.1)
+        assert executor.get_stats()["secs_since_reset"] >= 1
+
+        executor.reset_stats()
+        stats = executor.get_stats()
+        assert stats["cache_hits"] == 0
+        assert stats["cache_misses"] == 0
+        assert stats["secs_since_reset"] == 0
+        assert "cache_hit_rate_percent" not in stats
+        assert executor.cache_analytics() == {}
+
+        assert executor.get_cached("search", "rust") == "results"
+        assert executor.get_stats()["cache_hits"] == 1
+
+    def test_global_execution_limit_spans_executors(self):
+        """Test the process-wide cap on in-flight executions."""
+        core = pytest.importorskip("fast_crewai._core")
+        first = core.RustToolExecutor(10)
+        second = core.RustToolExecutor(10)
+        core.set_global_execution_limit(2)
+        try:
+            first.begin_execution("search", "{}")
+            second.begin_execution("search", "{}")
+            assert core.global_executions_in_flight() == 2
+            with pytest.raises(RuntimeError, match="Global execution limit of 2 reached"):
+                first.begin_execution("search", "{}")
+            assert core.global_executions_in_flight() == 2
+
+            second.end_execution()
+            first.begin_execution("search", "{}")
+
+            # In blocking mode a waiting execution proceeds once another ends
+            core.set_global_execution_limit(2, blocking=True)
+            started = threading.Event()
+            waiter = threading.Thread(
+                target=lambda: (second.begin_execution("search", "{}"), started.set())
+            )
+            waiter.start()
+            assert not started.wait(0.2)
+            first.end_execution()
+            assert started.wait(5)
+            waiter.join()
+        finally:
+            core.set_global_execution_limit(None)
+            first.end_execution()
+            second.end_execution()
+        assert core.global_executions_in_flight() == 0
+
+    def test_per_tool_stats_breakdown(self):
+        """Test execution and cache counters broken down by tool."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10)
+        for _ in range(3):
+            executor.begin_execution("search", "{}")
+            executor.end_execution()
+        executor.begin_execution("fetch", "{}")
+        executor.end_execution()
+        executor.cache_result("search", "rust", "results")
+        executor.get_cached("search", "rust")
+        executor.get_cached("fetch", "page")
+
+        assert executor.get_tool_stats("search") == {
+            "executions": 3, "hits": 1, "misses": 0, "stores": 1, "evictions": 0,
+        }
+        assert executor.get_tool_stats("unknown")["executions"] == 0
+        all_stats = executor.get_all_tool_stats()
+        assert set(all_stats) == {"search", "fetch"}
+        assert all_stats["fetch"]["executions"] == 1
+        assert all_stats["fetch"]["misses"] == 1
+
+    def test_check_timeouts_reports_stuck_executions(self):
+        """Test that long-running executions are reported by id and can be ended."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(2)
+        stuck = executor.begin_execution("fetch", "{}")
+        time.sleep(1.1)
+        fresh = executor.begin_execution("fetch", "{}")
+        assert stuck != fresh
+        assert not executor.can_execute()
+
+        assert executor.check_timeouts(1) == [stuck]
+        assert executor.check_timeouts(0) == [stuck, fresh]
+
+        executor.end_execution(stuck)
+        executor.end_execution(stuck)
+        assert executor.can_execute()
+        assert executor.check_timeouts(1) == []
+        executor.end_execution()
+        assert executor.check_timeouts(0) == []
+
+    def test_batch_validate_detailed_reports_errors(self):
+        """Test per-entry error messages and failure counting in batch validation."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10)
+        results = executor.batch_validate_detailed(['{"q": 1}', "{broken", "[]", ""])
+
+        assert [valid for valid, _ in results] == [True, False, True, False]
+        assert results[0][1] is None
+        assert results[1][1].startswith("Invalid JSON arguments: ")
+        assert "line 1" in results[1][1]
+        assert executor.get_stats()["validation_failures"] == 2
+
+    def test_normalize_with_defaults_fills_missing_args(self):
+        """Test shallow and deep merging of registered default arguments."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10)
+        executor.register_defaults("search", '{"limit": 10, "options": {"safe": true, "lang": "en"}}')
+
+        merged = executor.normalize_with_defaults("search", '{"query": "rust", "options": {"lang": "de"}}')
+        assert json.loads(merged) == {"query": "rust", "limit": 10, "options": {"lang": "de"}}
+        assert merged == executor.normalize_with_defaults("search", '{"limit":10,"options":{"lang":"de"},"query":"rust"}')
+
+        deep = executor.normalize_with_defaults("search", '{"query": "rust", "options": {"lang": "de"}}', deep=True)
+        assert json.loads(deep)["options"] == {"lang": "de", "safe": True}
+
+        assert json.loads(executor.normalize_with_defaults("fetch", '{"b": 1, "a": 2}')) == {"a": 2, "b": 1}
+        with pytest.raises(ValueError, match="must be a JSON object"):
+            executor.normalize_with_defaults("search", "[1]")
+        with pytest.raises(ValueError, match="Invalid defaults"):
+            executor.register_defaults("fetch", "{not json")
+        with pytest.raises(ValueError, match="expected a JSON object"):
+            executor.register_defaults("fetch", "[]")
+
+    def test_invalidate_tool_keeps_other_tools_warm(self):
+        """Test dropping one tool's cached results across versions and pins."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10)
+        executor.cache_result("search", "rust", "v1 results")
+        executor.register_tool_version("search", "2")
+        executor.cache_result("search", "rust", "v2 results")
+        executor.cache_result("search", "python", "pinned")
+        executor.pin_cache("search", "python")
+        executor.cache_result("search_web", "rust", "web")
+        executor.cache_result("fetch", "page", "body")
+
+        assert executor.invalidate_tool("search") == 3
+        assert executor.get_cached("search", "rust") is None
+        assert executor.get_cached("search_web", "rust") == "web"
+        assert executor.get_cached("fetch", "page") == "body"
+        assert executor.invalidate_tool("search") == 0
+
+    def test_retry_bookkeeping_enforces_max_retries(self):
+        """Test counting attempts per execution id against a retry limit."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10)
+        execution_id = executor.begin_execution("fetch", "{}")
+        assert executor.should_retry(execution_id, 0)
+
+        assert executor.record_attempt(execution_id) == 1
+        executor.end_execution(execution_id)
+        assert executor.should_retry(execution_id, 2)
+        assert executor.record_attempt(execution_id) == 2
+        assert executor.record_attempt(execution_id) == 3
+        assert not executor.should_retry(execution_id, 2)
+        assert executor.record_attempt("other") == 1
+
+        assert executor.clear_attempts(execution_id)
+        assert not executor.clear_attempts(execution_id)
+        assert executor.record_attempt(execution_id) == 1
+
+    def test_compress_cache_round_trips_results(self):
+        """Test that compressed caching is transparent and reports its ratio."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10, compress_cache=True)
+        large = json.dumps([{"id": i, "status": "ok"} for i in range(200)])
+        executor.cache_result("fetch", "large", large)
+        executor.cache_result("fetch", "small", "tiny")
+
+        assert executor.get_cached("fetch", "large") == large
+        assert executor.get_cached("fetch", "small") == "tiny"
+        assert executor.get_cached_batch([("fetch", "large")]) == [large]
+
+        stats = executor.get_stats()
+        assert stats["compression_input_bytes"] == len(large)
+        assert 0 < stats["compression_output_bytes"] < len(large)
+        assert stats["compression_ratio_percent"] > 100
+
+        plain = core.RustToolExecutor(10)
+        plain.cache_result("fetch", "large", large)
+        assert plain.get_cached("fetch", "large") == large
+        assert "compression_ratio_percent" not in plain.get_stats()
+
+    def test_export_cache_restores_live_entries(self, tmp_path):
+        """Test persisting the cache to a file and loading it into a fresh executor."""
+        core = pytest.importorskip("fast_crewai._core")
+        path = str(tmp_path / "cache.json")
+        executor = core.RustToolExecutor(10, cache_ttl_secs=60)
+        executor.cache_result("search", "rust", "results")
+        executor.cache_result("fetch", "page", "body")
+        executor.pin_cache("fetch", "page")
+        assert executor.export_cache(path) == 2
+
+        with open(path) as f:
+            saved = json.load(f)
+        for entry in saved["entries"]:
+            entry["expires_at"] = 0
+        stale_path = str(tmp_path / "stale.json")
+        with open(stale_path, "w") as f:
+            json.dump(saved, f)
+
+        restored = core.RustToolExecutor(10, cache_ttl_secs=60)
+        assert restored.import_cache(path) == 2
+        assert restored.get_cached("search", "rust") == "results"
+        assert restored.get_stats()["pinned_entries"] == 1
+
+        stale = core.RustToolExecutor(10, cache_ttl_secs=60)
+        assert stale.import_cache(stale_path) == 1
+        assert stale.get_cached("search", "rust") is None
+        assert stale.get_cached("fetch", "page") == "body"
+        with pytest.raises(IOError, match="Failed to read cache file"):
+            stale.import_cache(str(tmp_path / "missing.json"))
+
+    def test_cache_keys_separate_tool_names_from_versions(self):
+        """Test that a tool named like name@version doesn't share another tool's entries."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10)
+        executor.register_tool_version("a", "1")
+        executor.cache_result("a", '{"q": 1}', "versioned tool")
+
+        assert executor.get_cached("a@1", '{"q": 1}') is None
+        executor.cache_result("a@1", '{"q": 1}', "oddly named tool")
+        assert executor.get_cached("a", '{ "q" : 1 }') == "versioned tool"
+        assert executor.get_cached_batch([("a@1", '{"q":1}'), ("a", '{"q": 2}')]) == ["oddly named tool", None]
+
+    def test_compact_cache_drops_rarely_hit_unpinned_entries(self):
+        """Test that compaction removes entries below min_hits, keeps pins and counts removals."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10)
+        executor.cache_result("search", "popular", "popular results")
+        executor.cache_result("search", "once", "one hit")
+        executor.cache_result("search", "never", "no hits")
+        executor.cache_result("search", "pinned", "pinned results")
+        executor.pin_cache("search", "pinned")
+        for _ in range(2):
+            executor.get_cached("search", "popular")
+        executor.get_cached("search", "once")
+
+        assert executor.compact_cache(2) == 2
+        assert executor.get_cache_size() == 2
+        assert executor.get_cached("search", "popular") == "popular results"
+        assert executor.get_cached("search", "pinned") == "pinned results"
+        assert executor.get_cached("search", "once") is None
+        assert executor.get_cached("search", "never") is None
+        assert executor.compact_cache(0) == 0
+
+    def test_import_cache_reads_unversioned_file(self, tmp_path):
+        """Test that a cache file written before versioning (no format_version) still loads."""
+        core = pytest.importorskip("fast_crewai._core")
+        path = tmp_path / "cache.json"
+        executor = core.RustToolExecutor(10, cache_ttl_secs=60)
+        executor.cache_result("search", "rust", "results")
+        executor.export_cache(str(path))
+        legacy = json.loads(path.read_text())
+        del legacy["format_version"]
+        path.write_text(json.dumps(legacy))
+
+        restored = core.RustToolExecutor(10, cache_ttl_secs=60)
+        assert restored.import_cache(str(path)) == 1
+        assert restored.get_cached("search", "rust") == "results"
+
+    def test_compression_stats_skip_refused_results(self):
+        """Test that compression bytes are only counted for results the cache keeps."""
+        core = pytest.importorskip("fast_crewai._core")
+        executor = core.RustToolExecutor(10, max_cache_entries=1, compress_cache=True)
+        large = json.dumps([{"id": i, "status": "ok"} for i in range(200)])
+        assert executor.cache_result("fetch", "pinned", large)
+        executor.pin_cache("fetch", "pinned")
+        before = executor.get_stats()
+
+        assert not executor.cache_result("fetch", "refused", large)
+        stats = executor.get_stats()
+        assert stats["compression_input_bytes"] == before["compression_input_bytes"] == len(large)
+        assert stats["compression_output_bytes"] == before["compression_output_bytes"]
+
+
 if __name__ == "__main__":
     pytest.main([__file__])